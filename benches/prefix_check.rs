@@ -0,0 +1,91 @@
+//! How long it takes to find the prefix of a message from the cache `prefix_check` uses, compared
+//! to asking SQLite for the guild's prefixes on every message like it used to
+//!
+//! Run it with `cargo bench`
+
+use std::{
+    hint::black_box,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use discord_search::{
+    cmd_prefix::{command_prefix, load_prefixes},
+    globals::CmdInfo,
+    migrations::migrate,
+    GENERAL_GROUP,
+};
+use serenity::model::id::{ChannelId, GuildId};
+use sqlx::{
+    any::{AnyConnectOptions, AnyPoolOptions},
+    query, AnyPool,
+};
+use tokio::runtime::Runtime;
+
+const GUILDS: u64 = 1000;
+const CACHED_RUNS: u32 = 1_000_000;
+const QUERIED_RUNS: u32 = 10_000;
+const MESSAGES: [&str; 4] = ["hello everyone", "?info", "i think so", "?prefix list"];
+
+async fn database() -> AnyPool {
+    let db = AnyPoolOptions::new()
+        .max_connections(1)
+        .connect_with(AnyConnectOptions::from_str("sqlite::memory:").expect("Bad database URL"))
+        .await
+        .expect("Couldn't open the database");
+    migrate(&db).await;
+
+    for guild_id in 0..GUILDS {
+        query("INSERT INTO guild_prefixes (guild_id, prefix) VALUES($1, $2)")
+            .bind(guild_id as i64)
+            .bind("?")
+            .execute(&db)
+            .await
+            .expect("Couldn't add a prefix");
+    }
+    load_prefixes(&db).await;
+    db
+}
+
+fn message(run: u32) -> (GuildId, &'static str) {
+    (
+        GuildId(u64::from(run) % GUILDS),
+        MESSAGES[run as usize % MESSAGES.len()],
+    )
+}
+
+fn main() {
+    let runtime = Runtime::new().expect("Couldn't start the runtime");
+    let db = runtime.block_on(database());
+    CmdInfo::set(&[&GENERAL_GROUP]);
+
+    // Not async at all, so nothing on this path can wait for the database
+    let start = Instant::now();
+    for run in 0..CACHED_RUNS {
+        let (guild_id, content) = message(run);
+        black_box(command_prefix(guild_id, ChannelId(1), content));
+    }
+    let cached = start.elapsed() / CACHED_RUNS;
+
+    let queried: Duration = runtime.block_on(async {
+        let start = Instant::now();
+        for run in 0..QUERIED_RUNS {
+            let (guild_id, _) = message(run);
+            black_box(
+                query("SELECT prefix FROM guild_prefixes WHERE guild_id = $1")
+                    .bind(guild_id.0 as i64)
+                    .fetch_all(&db)
+                    .await
+                    .expect("Couldn't get the prefixes"),
+            );
+        }
+        start.elapsed() / QUERIED_RUNS
+    });
+
+    println!("From the cache:    {:?} per message", cached);
+    println!("Asking SQLite:     {:?} per message", queried);
+    println!(
+        "The cache is {:.0} times faster",
+        queried.as_secs_f64() / cached.as_secs_f64()
+    );
+}
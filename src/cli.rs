@@ -0,0 +1,100 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::globals::ENV_PREFIX;
+
+const DEFAULT_CONFIG_FILE: &str = "search-config.toml";
+
+const USAGE: &str = "Usage: discord-search [options]
+
+Options:
+  --config <path>     The config file to use, instead of search-config.toml in the data directory
+  --data-dir <path>   Where to put and read the files from, instead of the exe's directory
+  --check-config      Check the config and exit without connecting to Discord
+  --help              Show this
+
+DISCORD_SEARCH_CONFIG and DISCORD_SEARCH_DATA_DIR do the same, the options win if both are set
+Every field in the config file can be set with DISCORD_SEARCH_ and its name in upper case too, like DISCORD_SEARCH_TOKEN";
+
+/// The command line options, falling back to the environment variables with the same names
+pub struct CliArgs {
+    config: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    check_config: bool,
+}
+
+impl CliArgs {
+    /// Prints the usage and exits if the options don't make sense
+    pub fn parse() -> Self {
+        let mut cli_args = CliArgs {
+            config: env_path("CONFIG"),
+            data_dir: env_path("DATA_DIR"),
+            check_config: false,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            match name.as_str() {
+                "--help" | "-h" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                "--check-config" => cli_args.check_config = true,
+                "--config" => cli_args.config = Some(value_of(&name, value, &mut args).into()),
+                "--data-dir" => cli_args.data_dir = Some(value_of(&name, value, &mut args).into()),
+                _ => exit_with_usage(&format!("I don't know what {} is", name)),
+            }
+        }
+
+        // Relative paths are from where I was started, not the data directory
+        cli_args.config = cli_args.config.map(absolute);
+        cli_args.data_dir = cli_args.data_dir.map(absolute);
+        cli_args
+    }
+
+    pub fn config(&self) -> PathBuf {
+        self.config
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE))
+    }
+
+    pub fn data_dir(&self) -> Option<&Path> {
+        self.data_dir.as_deref()
+    }
+
+    pub fn check_config(&self) -> bool {
+        self.check_config
+    }
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(format!("{}{}", ENV_PREFIX, name)).map(PathBuf::from)
+}
+
+fn value_of(name: &str, value: Option<String>, args: &mut impl Iterator<Item = String>) -> String {
+    value
+        .or_else(|| args.next())
+        .unwrap_or_else(|| exit_with_usage(&format!("{} needs a path after it", name)))
+}
+
+fn absolute(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        return path;
+    }
+    match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path,
+    }
+}
+
+fn exit_with_usage(err: &str) -> ! {
+    eprintln!("{}\n\n{}", err, USAGE);
+    process::exit(2);
+}
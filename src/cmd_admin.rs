@@ -0,0 +1,501 @@
+use std::{sync::Arc, time::Duration};
+
+use arc_swap::ArcSwapOption;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serenity::{
+    builder::CreateEmbed,
+    client::{bridge::gateway::ShardManager, Context},
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, gateway::Activity, id::GuildId},
+    prelude::Mutex,
+};
+use sqlx::{
+    any::{AnyKind, AnyRow, AnyTypeInfo},
+    query, Any, AnyPool, Column, Connection, Decode, Row, Type, ValueRef,
+};
+
+use crate::{
+    db,
+    definitions::{truncate, MAX_DESCRIPTION_LEN},
+    globals::{DatabaseKey, ShardManagerKey},
+    log,
+    logging::{self, log_at, Level},
+    migrations::database_version,
+    send_embed, send_pages,
+};
+
+const GUILDS_PER_PAGE: usize = 15;
+const MAX_CELL_LEN: usize = 30;
+const READ_ONLY_STATEMENTS: [&str; 3] = ["select", "with", "explain"];
+
+static STARTED_AT: Lazy<DateTime<Utc>> = Lazy::new(Utc::now);
+static ACTIVITY: Lazy<ArcSwapOption<Activity>> = Lazy::new(ArcSwapOption::empty);
+
+/// Remembers when I started for the `stats` command
+pub fn set_started_at() {
+    Lazy::force(&STARTED_AT);
+}
+
+/// The one set with `setactivity` or `Playing @[name] help`
+pub fn activity(name: &str) -> Activity {
+    match ACTIVITY.load_full() {
+        Some(activity) => (*activity).clone(),
+        None => Activity::playing(format!("@{} help", name).as_str()),
+    }
+}
+
+#[command("stats")]
+#[aliases("statistics")]
+#[help_available(false)]
+#[description = "How many guilds I'm in, how fast I am and all that"]
+async fn cmd_stats(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_ids = ctx.cache.guilds().await;
+    let mut member_count = 0;
+    for guild_id in guild_ids.iter() {
+        member_count += ctx
+            .cache
+            .guild_field(guild_id, |guild| guild.member_count)
+            .await
+            .unwrap_or_default();
+    }
+
+    let latency = match shard_manager(ctx).await {
+        Some(manager) => {
+            let manager = manager.lock().await;
+            let runners = manager.runners.lock().await;
+            let latencies: Vec<Duration> = runners
+                .values()
+                .filter_map(|runner| runner.latency)
+                .collect();
+            if latencies.is_empty() {
+                "Not measured yet".to_string()
+            } else {
+                let total: Duration = latencies.iter().sum();
+                format!("{}ms", total.as_millis() / latencies.len() as u128)
+            }
+        }
+        None => "No idea".to_string(),
+    };
+
+    let database = match ctx.data.read().await.get::<DatabaseKey>().cloned() {
+        Some(db) => {
+            let kind = match db.any_kind() {
+                AnyKind::Sqlite => "SQLite",
+                AnyKind::Postgres => "Postgres",
+            };
+            match database_version(&db).await {
+                Ok(version) => format!("{}, schema version {}", kind, version),
+                Err(err) => {
+                    log(ctx, format!("Couldn't get the schema version: {}", err)).await;
+                    format!("{}, no idea which schema version", kind)
+                }
+            }
+        }
+        None => "Lost it".to_string(),
+    };
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("How I'm doing")
+        .field("Guilds", guild_ids.len(), true)
+        .field("Members", member_count, true)
+        .field("Channels", ctx.cache.guild_channel_count().await, true)
+        .field("Shards", ctx.cache.shard_count().await, true)
+        .field("Latency", latency, true)
+        .field("Started", format!("<t:{}:R>", STARTED_AT.timestamp()), true)
+        .field("Version", env!("CARGO_PKG_VERSION"), true)
+        .field("Database", database, true);
+    send_embed(ctx, msg, false, embed).await;
+    Ok(())
+}
+
+#[command("guilds")]
+#[aliases("servers")]
+#[help_available(false)]
+#[description = "Every guild I'm in with their member counts, the biggest ones first"]
+async fn cmd_guilds(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut guilds = Vec::new();
+    for guild_id in ctx.cache.guilds().await {
+        if let Some((name, member_count)) = ctx
+            .cache
+            .guild_field(guild_id, |guild| (guild.name.clone(), guild.member_count))
+            .await
+        {
+            guilds.push((guild_id, name, member_count));
+        }
+    }
+
+    if guilds.is_empty() {
+        let mut embed = CreateEmbed::default();
+        embed.description("I'm not in any guilds yet, how lonely..");
+        send_embed(ctx, msg, false, embed).await;
+        return Ok(());
+    }
+
+    guilds.sort_by(|(_, _, a), (_, _, b)| b.cmp(a));
+    let member_count: u64 = guilds.iter().map(|(_, _, member_count)| member_count).sum();
+    let title = format!("In {} guilds with {} members", guilds.len(), member_count);
+
+    let pages = guilds
+        .chunks(GUILDS_PER_PAGE)
+        .map(|chunk| {
+            let lines: Vec<String> = chunk
+                .iter()
+                .map(|(guild_id, name, member_count)| {
+                    format!("**{}** `{}`: {} members", name, guild_id, member_count)
+                })
+                .collect();
+            let mut embed = CreateEmbed::default();
+            embed.title(&title).description(lines.join("\n"));
+            embed
+        })
+        .collect();
+    send_pages(ctx, msg, pages).await;
+    Ok(())
+}
+
+#[command("leave")]
+#[help_available(false)]
+#[description = "Leave a guild, I forget everything about it just like when I'm kicked"]
+#[usage = "[guild ID]"]
+#[example = "81384788765712384"]
+async fn cmd_leave(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let guild_id = match args.single::<u64>() {
+        Ok(guild_id) => GuildId(guild_id),
+        Err(_) => {
+            embed
+                .title("I need the ID of the guild to leave")
+                .description("You can find it with `guilds`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+    let name = match ctx
+        .cache
+        .guild_field(guild_id, |guild| guild.name.clone())
+        .await
+    {
+        Some(name) => name,
+        None => {
+            embed.description("I'm not in that guild anyway..");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+
+    let is_error = match guild_id.leave(ctx).await {
+        Ok(_) => {
+            log_at(
+                ctx,
+                Level::Info,
+                format!("Left {} ({}) because my master told me to", name, guild_id),
+            );
+            embed.description(format!("Left **{}**, bye bye 👋", name));
+            false
+        }
+        Err(err) => {
+            log(
+                ctx,
+                format!("Couldn't leave the guild {}: {}", guild_id, err),
+            )
+            .await;
+            embed
+                .title("I couldn't leave, they won't let me go..")
+                .description(err);
+            true
+        }
+    };
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[command("shutdown")]
+#[aliases("stop")]
+#[help_available(false)]
+#[description = "Log off every shard and stop running, you'll have to start me again yourself"]
+async fn cmd_shutdown(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let manager = match shard_manager(ctx).await {
+        Some(manager) => manager,
+        None => {
+            log(ctx, "Couldn't get the shard manager to shut down").await;
+            embed.description("I can't find my off switch..");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+
+    embed.description("Good night 💤");
+    send_embed(ctx, msg, false, embed).await;
+    log_at(
+        ctx,
+        Level::Info,
+        "Shutting down because my master told me to",
+    );
+    logging::flush(ctx).await;
+    manager.lock().await.shutdown_all().await;
+    Ok(())
+}
+
+#[command("setactivity")]
+#[aliases("activity", "presence")]
+#[help_available(false)]
+#[description = "Change what I'm doing on every shard\nType `setactivity reset` to go back to `Playing @[my name] help`"]
+#[usage = "[playing, listening, watching or competing] [text]"]
+#[example = "watching you search things"]
+async fn cmd_setactivity(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let kind = args.single::<String>().unwrap_or_default().to_lowercase();
+    let text = args.rest().trim();
+    let activity = match kind.as_str() {
+        "reset" => None,
+        _ if text.is_empty() => {
+            embed
+                .title("I need to know what I'm doing")
+                .description("Like `setactivity watching you search things`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+        "playing" => Some(Activity::playing(text)),
+        "listening" => Some(Activity::listening(text)),
+        "watching" => Some(Activity::watching(text)),
+        "competing" => Some(Activity::competing(text)),
+        _ => {
+            embed
+                .title(format!("I don't know how to be {}", kind))
+                .description("I can be `playing`, `listening`, `watching` or `competing`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+
+    ACTIVITY.store(activity.map(Arc::new));
+    let activity = activity_for(ctx).await;
+    match shard_manager(ctx).await {
+        Some(manager) => {
+            let manager = manager.lock().await;
+            for runner in manager.runners.lock().await.values() {
+                runner.runner_tx.set_activity(Some(activity.clone()));
+            }
+            embed.description("Changed it on every shard ✨");
+        }
+        None => {
+            log(ctx, "Couldn't get the shard manager to set the activity").await;
+            ctx.set_activity(activity).await;
+            embed.description(
+                "Changed it, but only on this shard, the others will when they reconnect",
+            );
+        }
+    }
+    send_embed(ctx, msg, false, embed).await;
+    Ok(())
+}
+
+async fn activity_for(ctx: &Context) -> Activity {
+    activity(&ctx.cache.current_user().await.name)
+}
+
+#[command("sql")]
+#[aliases("query")]
+#[help_available(false)]
+#[description = "Look something up in the database, it can't change anything\nOnly `SELECT`, `WITH` and `EXPLAIN`, one query at a time"]
+#[usage = "[query]"]
+#[example = "SELECT guild_id, prefix FROM guild_prefixes LIMIT 5"]
+async fn cmd_sql(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let sql = strip_code_block(args.rest());
+    if let Some(problem) = read_only_problem(sql) {
+        embed
+            .title(problem)
+            .description("Like `sql SELECT * FROM guild_prefixes`");
+        send_embed(ctx, msg, true, embed).await;
+        return Ok(());
+    }
+
+    let db = match db(ctx, msg, "sql").await {
+        Some(db) => db,
+        None => return Ok(()),
+    };
+
+    let is_error = match read_only_query(&db, sql).await {
+        Ok(rows) if rows.is_empty() => {
+            embed.description("Nothing matched, no rows at all");
+            false
+        }
+        Ok(rows) => {
+            let (table, shown) = table(&rows);
+            embed
+                .title(if shown == rows.len() {
+                    format!("{} rows", rows.len())
+                } else {
+                    format!("The first {} of {} rows", shown, rows.len())
+                })
+                .description(table);
+            false
+        }
+        Err(err) => {
+            embed
+                .title("The database didn't like that..")
+                .description(truncate(&err.to_string(), MAX_DESCRIPTION_LEN));
+            true
+        }
+    };
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+/// The query without the code block around it and the `;` after it
+fn strip_code_block(sql: &str) -> &str {
+    let sql = sql.trim();
+    let sql = match sql.strip_prefix("```") {
+        Some(sql) => {
+            let sql = sql.strip_suffix("```").unwrap_or(sql);
+            sql.strip_prefix("sql")
+                .filter(|rest| rest.starts_with(char::is_whitespace))
+                .unwrap_or(sql)
+        }
+        None => sql,
+    };
+    sql.trim().trim_end_matches(';').trim_end()
+}
+
+fn read_only_problem(sql: &str) -> Option<&'static str> {
+    if sql.is_empty() {
+        return Some("I need a query to run");
+    }
+    if sql.contains(';') {
+        return Some("One query at a time please");
+    }
+    let statement = sql
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if !READ_ONLY_STATEMENTS.contains(&statement.as_str()) {
+        return Some("I can only look things up, start it with `SELECT`, `WITH` or `EXPLAIN`");
+    }
+    None
+}
+
+/// Runs the query in a transaction that can't write anything and is rolled back anyway
+async fn read_only_query(db: &AnyPool, sql: &str) -> sqlx::Result<Vec<AnyRow>> {
+    let is_sqlite = db.any_kind() == AnyKind::Sqlite;
+    let mut conn = db.acquire().await?;
+    if is_sqlite {
+        query("PRAGMA query_only = ON").execute(&mut *conn).await?;
+    }
+
+    let rows = async {
+        let mut transaction = conn.begin().await?;
+        if !is_sqlite {
+            query("SET TRANSACTION READ ONLY")
+                .execute(&mut transaction)
+                .await?;
+        }
+        let rows = query(sql).fetch_all(&mut transaction).await;
+        transaction.rollback().await?;
+        rows
+    }
+    .await;
+
+    if is_sqlite
+        && query("PRAGMA query_only = OFF")
+            .execute(&mut *conn)
+            .await
+            .is_err()
+    {
+        // Nobody else should get a connection that can't write
+        drop(conn.detach());
+    }
+    rows
+}
+
+/// The rows in a code block, with as many of them as fit in an embed
+fn table(rows: &[AnyRow]) -> (String, usize) {
+    let clean = |text: &str| truncate(&text.replace('\n', " ").replace('`', "'"), MAX_CELL_LEN);
+    let columns: Vec<String> = rows[0]
+        .columns()
+        .iter()
+        .map(|column| clean(column.name()))
+        .collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| (0..columns.len()).map(|i| clean(&cell(row, i))).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|name| name.chars().count()).collect();
+    for row in cells.iter() {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |row: &[String]| {
+        row.iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let separator = widths
+        .iter()
+        .map(|width| "-".repeat(*width))
+        .collect::<Vec<String>>()
+        .join("-+-");
+
+    let mut table = format!("```\n{}\n{}\n", line(&columns), separator);
+    let mut len = table.chars().count();
+    let mut shown = 0;
+    for row in cells.iter() {
+        let line = line(row);
+        len += line.chars().count() + 1;
+        if len + 3 > MAX_DESCRIPTION_LEN {
+            break;
+        }
+        table.push_str(&line);
+        table.push('\n');
+        shown += 1;
+    }
+    table.push_str("```");
+    (table, shown)
+}
+
+/// The value as text whatever its type is
+fn cell(row: &AnyRow, index: usize) -> String {
+    let value = match row.try_get_raw(index) {
+        Ok(value) => value,
+        Err(_) => return "?".to_string(),
+    };
+    if value.is_null() {
+        return "NULL".to_string();
+    }
+    let type_info = value.type_info().into_owned();
+
+    decoded::<String>(row, index, &type_info)
+        .or_else(|| decoded::<i64>(row, index, &type_info))
+        .or_else(|| decoded::<i32>(row, index, &type_info))
+        .or_else(|| decoded::<f64>(row, index, &type_info))
+        .or_else(|| decoded::<f32>(row, index, &type_info))
+        .or_else(|| decoded::<bool>(row, index, &type_info))
+        .unwrap_or_else(|| "?".to_string())
+}
+
+/// `try_get` panics instead of failing when the type is wrong, so it's checked first
+fn decoded<T>(row: &AnyRow, index: usize, type_info: &AnyTypeInfo) -> Option<String>
+where
+    T: for<'r> Decode<'r, Any> + Type<Any> + ToString,
+{
+    if !T::compatible(type_info) {
+        return None;
+    }
+    row.try_get_unchecked::<T, _>(index)
+        .ok()
+        .map(|value| value.to_string())
+}
+
+async fn shard_manager(ctx: &Context) -> Option<Arc<Mutex<ShardManager>>> {
+    ctx.data.read().await.get::<ShardManagerKey>().cloned()
+}
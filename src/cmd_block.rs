@@ -0,0 +1,488 @@
+use std::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use chrono::{Duration, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    framework::standard::{
+        macros::{check, command},
+        Args, CommandOptions, CommandResult, Reason,
+    },
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, UserId},
+    },
+    utils::{parse_channel, parse_username},
+};
+use sqlx::{query, AnyPool, Row};
+
+use crate::{
+    db,
+    definitions::{truncate, MAX_DESCRIPTION_LEN},
+    globals::{BotInfo, DatabaseKey},
+    log, send_embed,
+};
+
+const RELOAD_SECONDS: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BlockKind {
+    User,
+    Guild,
+    Channel,
+}
+
+impl BlockKind {
+    fn parse(kind: &str) -> Option<Self> {
+        match kind.to_lowercase().as_str() {
+            "user" | "member" => Some(BlockKind::User),
+            "guild" | "server" => Some(BlockKind::Guild),
+            "channel" => Some(BlockKind::Channel),
+            _ => None,
+        }
+    }
+
+    /// The ID in a mention or just the number
+    fn parse_id(&self, id: &str) -> Option<u64> {
+        match self {
+            BlockKind::User => parse_username(id),
+            BlockKind::Channel => parse_channel(id),
+            BlockKind::Guild => None,
+        }
+        .or_else(|| id.parse().ok())
+    }
+}
+
+impl Display for BlockKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BlockKind::User => "user",
+            BlockKind::Guild => "guild",
+            BlockKind::Channel => "channel",
+        })
+    }
+}
+
+#[derive(Clone)]
+struct Block {
+    reason: Option<String>,
+    /// In milliseconds since the Unix epoch, `None` if it's forever
+    expires_at: Option<i64>,
+    /// Whether someone was told about it already, they're only told once so blocking a spammer
+    /// doesn't make me spam too
+    is_notified: bool,
+}
+
+impl Block {
+    fn new(reason: Option<String>, expires_at: Option<i64>) -> Self {
+        Block {
+            reason,
+            expires_at,
+            is_notified: false,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now().timestamp_millis())
+    }
+}
+
+static BLOCKS: Lazy<DashMap<(BlockKind, u64), Block>> = Lazy::new(DashMap::new);
+static RELOADING: AtomicBool = AtomicBool::new(false);
+
+pub async fn load_blocks(db: &AnyPool) {
+    query("DELETE FROM blocks WHERE expires_at <= $1")
+        .bind(Utc::now().timestamp_millis())
+        .execute(db)
+        .await
+        .expect("Couldn't delete the expired blocks");
+
+    let blocks = read_blocks(db)
+        .await
+        .unwrap_or_else(|err| panic!("Couldn't load the blocks: {}", err));
+    for (key, block) in blocks {
+        BLOCKS.insert(key, block);
+    }
+}
+
+/// The blocks in the database that didn't end yet
+async fn read_blocks(db: &AnyPool) -> Result<Vec<((BlockKind, u64), Block)>, String> {
+    let rows = query(
+        "SELECT kind, id, reason, expires_at FROM blocks WHERE expires_at IS NULL OR expires_at > $1",
+    )
+    .bind(Utc::now().timestamp_millis())
+    .fetch_all(db)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let mut blocks = Vec::new();
+    for row in rows.iter() {
+        let kind: String = row.try_get(0).map_err(|err| err.to_string())?;
+        let id: i64 = row.try_get(1).map_err(|err| err.to_string())?;
+        let kind = BlockKind::parse(&kind)
+            .ok_or_else(|| format!("There's an unknown kind of block: {}", kind))?;
+        blocks.push((
+            (kind, id as u64),
+            Block::new(
+                row.try_get(2).map_err(|err| err.to_string())?,
+                row.try_get(3).map_err(|err| err.to_string())?,
+            ),
+        ));
+    }
+    Ok(blocks)
+}
+
+/// Reads the blocks again every `RELOAD_SECONDS` so the ones made or removed by other processes
+/// sharing the database work here too, only the first call does anything
+pub fn reload_blocks_periodically(ctx: &Context) {
+    if RELOADING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(RELOAD_SECONDS));
+        loop {
+            interval.tick().await;
+            let db = match ctx.data.read().await.get::<DatabaseKey>().cloned() {
+                Some(db) => db,
+                None => {
+                    log(&ctx, "Couldn't get the database to reload the blocks").await;
+                    continue;
+                }
+            };
+            let blocks = match read_blocks(&db).await {
+                Ok(blocks) => blocks,
+                Err(err) => {
+                    log(&ctx, format!("Couldn't reload the blocks: {}", err)).await;
+                    continue;
+                }
+            };
+
+            BLOCKS.retain(|key, _| blocks.iter().any(|(other, _)| other == key));
+            for (key, block) in blocks {
+                let mut entry = BLOCKS.entry(key).or_insert_with(|| block.clone());
+                // Still the same block, so they were told about it already if they were
+                if entry.reason != block.reason || entry.expires_at != block.expires_at {
+                    *entry = block;
+                }
+            }
+        }
+    });
+}
+
+/// What to tell someone who's blocked, the text is the same as the framework's blocklists
+pub fn blocked_text(kind: BlockKind, reason: Option<&str>) -> String {
+    let who = match kind {
+        BlockKind::User => "you're",
+        BlockKind::Guild => "the guild or its owner is",
+        BlockKind::Channel => "the channel is",
+    };
+    match reason {
+        Some(reason) => format!("Oops, {} blocked to use this command: {}", who, reason),
+        None => format!(
+            "Oops, {} blocked to use this command for some reason..",
+            who
+        ),
+    }
+}
+
+/// The block on the user, the guild or the channel, the user's first
+fn find_block(
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+) -> Option<(BlockKind, u64)> {
+    let targets = [
+        Some((BlockKind::User, user_id.0)),
+        guild_id.map(|guild_id| (BlockKind::Guild, guild_id.0)),
+        Some((BlockKind::Channel, channel_id.0)),
+    ];
+
+    for key in targets.iter().flatten() {
+        let is_expired = match BLOCKS.get(key) {
+            Some(block) => block.is_expired(),
+            None => continue,
+        };
+        if is_expired {
+            BLOCKS.remove(key);
+            continue;
+        }
+        return Some(*key);
+    }
+    None
+}
+
+pub fn is_blocked(user_id: UserId, guild_id: Option<GuildId>, channel_id: ChannelId) -> bool {
+    find_block(user_id, guild_id, channel_id).is_some()
+}
+
+/// Why the command can't be used if the user, the guild or the channel is blocked
+///
+/// It's `Reason::User` with the text to tell them the first time, after that it's only logged
+pub fn blocked_reason(
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+) -> Option<Reason> {
+    let (kind, id) = find_block(user_id, guild_id, channel_id)?;
+    let mut block = BLOCKS.get_mut(&(kind, id))?;
+    if std::mem::replace(&mut block.is_notified, true) {
+        return Some(Reason::Log(format!("The {} {} is still blocked", kind, id)));
+    }
+
+    let mut text = blocked_text(kind, block.reason.as_deref());
+    if let Some(expires_at) = block.expires_at {
+        text.push_str(&format!("\nIt ends <t:{}:R>", expires_at / 1000));
+    }
+    Some(Reason::User(text))
+}
+
+#[check]
+#[name = "not_blocked"]
+#[display_in_help(false)]
+#[check_in_help(false)]
+pub async fn not_blocked(
+    _ctx: &Context,
+    msg: &Message,
+    _args: &mut Args,
+    _options: &CommandOptions,
+) -> Result<(), Reason> {
+    match blocked_reason(msg.author.id, msg.guild_id, msg.channel_id) {
+        Some(reason) => Err(reason),
+        None => Ok(()),
+    }
+}
+
+/// The kind and the ID at the start of the arguments
+fn parse_target(args: &mut Args) -> Option<(BlockKind, u64)> {
+    let kind = BlockKind::parse(&args.single::<String>().ok()?)?;
+    let id = kind.parse_id(&args.single::<String>().ok()?)?;
+    Some((kind, id))
+}
+
+/// Parses things like `30m`, `12h`, `7d` or `2w`, the inner `None` if it's too long to count
+fn parse_duration(duration: &str) -> Option<Option<Duration>> {
+    let unit = duration.chars().last()?;
+    let amount: i64 = duration[..duration.len() - unit.len_utf8()]
+        .parse()
+        .ok()
+        .filter(|amount| *amount > 0)?;
+    match unit {
+        's' => Some(Duration::try_seconds(amount)),
+        'm' => Some(Duration::try_minutes(amount)),
+        'h' => Some(Duration::try_hours(amount)),
+        'd' => Some(Duration::try_days(amount)),
+        'w' => Some(Duration::try_weeks(amount)),
+        _ => None,
+    }
+}
+
+#[command("block")]
+#[owners_only]
+#[help_available(false)]
+#[description = "Stop a user, a guild or a channel from using my commands\nAdd how long like `7d` before the reason if it shouldn't be forever"]
+#[usage = "[user, guild or channel] [ID or mention] [how long] [reason]"]
+#[example = "user @spammer 7d spamming the search commands"]
+async fn cmd_block(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let (kind, id) = match parse_target(&mut args) {
+        Some(target) => target,
+        None => {
+            embed
+                .title("I need to know if it's a user, a guild or a channel and its ID")
+                .description("Like `block user @spammer 7d spamming`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+    if kind == BlockKind::User && BotInfo::get().is_some_and(|info| info.owner().0 == id) {
+        embed.description("I can't block you, you're my master!");
+        send_embed(ctx, msg, true, embed).await;
+        return Ok(());
+    }
+
+    let expires_at = match args.current().and_then(parse_duration) {
+        Some(duration) => {
+            match duration.and_then(|duration| Utc::now().checked_add_signed(duration)) {
+                Some(expires_at) => {
+                    args.advance();
+                    Some(expires_at.timestamp_millis())
+                }
+                None => {
+                    embed
+                        .title("That's longer than I'll even be around")
+                        .description("Leave out how long to block them forever");
+                    send_embed(ctx, msg, true, embed).await;
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
+    let reason = Some(args.rest().trim())
+        .filter(|reason| !reason.is_empty())
+        .map(String::from);
+
+    let db = match db(ctx, msg, "block").await {
+        Some(db) => db,
+        None => return Ok(()),
+    };
+    let result = query(
+        "INSERT INTO blocks (kind, id, reason, expires_at) VALUES($1, $2, $3, $4)
+        ON CONFLICT (kind, id) DO UPDATE SET reason = excluded.reason, expires_at = excluded.expires_at",
+    )
+    .bind(kind.to_string())
+    .bind(id as i64)
+    .bind(reason.clone())
+    .bind(expires_at)
+    .execute(&db)
+    .await;
+
+    match result {
+        Ok(_) => {
+            BLOCKS.insert((kind, id), Block::new(reason, expires_at));
+            embed.description(match expires_at {
+                Some(expires_at) => format!(
+                    "Blocked the {} {} until <t:{}:f> 🔨",
+                    kind,
+                    id,
+                    expires_at / 1000
+                ),
+                None => format!("Blocked the {} {} 🔨", kind, id),
+            });
+            send_embed(ctx, msg, false, embed).await;
+        }
+        Err(err) => {
+            log(ctx, format!("Couldn't save a block: {}", err)).await;
+            embed
+                .title("Ugh, I couldn't write that down..")
+                .description(err);
+            send_embed(ctx, msg, true, embed).await;
+        }
+    }
+    Ok(())
+}
+
+#[command("unblock")]
+#[owners_only]
+#[help_available(false)]
+#[description = "Let a user, a guild or a channel use my commands again"]
+#[usage = "[user, guild or channel] [ID or mention]"]
+#[example = "user @spammer"]
+async fn cmd_unblock(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let (kind, id) = match parse_target(&mut args) {
+        Some(target) => target,
+        None => {
+            embed
+                .title("I need to know if it's a user, a guild or a channel and its ID")
+                .description("Like `unblock user @spammer`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+
+    let db = match db(ctx, msg, "unblock").await {
+        Some(db) => db,
+        None => return Ok(()),
+    };
+    let result = query("DELETE FROM blocks WHERE kind = $1 AND id = $2")
+        .bind(kind.to_string())
+        .bind(id as i64)
+        .execute(&db)
+        .await;
+
+    match result {
+        Ok(_) => {
+            let was_blocked = BLOCKS.remove(&(kind, id)).is_some();
+            embed.description(if was_blocked {
+                format!("Unblocked the {} {}, be nice this time", kind, id)
+            } else {
+                format!("The {} {} wasn't blocked anyway", kind, id)
+            });
+            send_embed(ctx, msg, false, embed).await;
+        }
+        Err(err) => {
+            log(ctx, format!("Couldn't delete a block: {}", err)).await;
+            embed.title("Ugh, I couldn't erase that..").description(err);
+            send_embed(ctx, msg, true, embed).await;
+        }
+    }
+    Ok(())
+}
+
+#[command("blocklist")]
+#[aliases("blocks", "blocked")]
+#[owners_only]
+#[help_available(false)]
+#[description = "See everyone and everything that's blocked"]
+async fn cmd_blocklist(ctx: &Context, msg: &Message) -> CommandResult {
+    BLOCKS.retain(|_, block| !block.is_expired());
+
+    let mut blocks: Vec<((BlockKind, u64), Block)> = BLOCKS
+        .iter()
+        .map(|entry| (*entry.key(), entry.value().clone()))
+        .collect();
+    blocks.sort_by_key(|(key, _)| *key);
+
+    let mut embed = CreateEmbed::default();
+    if blocks.is_empty() {
+        embed.description("Nobody's blocked, everyone's been nice ✨");
+    } else {
+        let lines: Vec<String> = blocks
+            .iter()
+            .map(|((kind, id), block)| {
+                let mut line = format!("**{}** {}", kind, id);
+                if let Some(expires_at) = block.expires_at {
+                    line.push_str(&format!(" until <t:{}:f>", expires_at / 1000));
+                }
+                if let Some(reason) = &block.reason {
+                    line.push_str(&format!(": {}", reason));
+                }
+                line
+            })
+            .collect();
+        embed
+            .title(format!("{} blocked", blocks.len()))
+            .description(truncate(&lines.join("\n"), MAX_DESCRIPTION_LEN));
+    }
+
+    send_embed(ctx, msg, false, embed).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        let cases = [
+            ("30s", Some(Duration::try_seconds(30))),
+            ("30m", Some(Duration::try_minutes(30))),
+            ("12h", Some(Duration::try_hours(12))),
+            ("7d", Some(Duration::try_days(7))),
+            ("2w", Some(Duration::try_weeks(2))),
+            ("99999999999999w", Some(None)),
+            ("9223372036854775807s", Some(None)),
+            ("0d", None),
+            ("-7d", None),
+            ("7", None),
+            ("7y", None),
+            ("spamming", None),
+            ("d", None),
+            ("", None),
+            ("7日", None),
+        ];
+
+        for (duration, expected) in cases.iter() {
+            assert_eq!(parse_duration(duration), *expected, "{:?}", duration);
+        }
+    }
+}
@@ -0,0 +1,720 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    framework::standard::{
+        macros::{check, command},
+        Args, CommandOptions, CommandResult, Reason,
+    },
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, UserId},
+    },
+};
+use sqlx::{any::AnyKind, query, AnyPool, Row};
+
+use crate::{
+    cmd_error, db,
+    globals::{BotConfig, ConfigProblem, DatabaseKey},
+    log, send_embed,
+};
+
+const PRUNE_SECONDS: u64 = 600;
+
+/// What a bucket counts the uses for
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketScope {
+    Global,
+    #[default]
+    User,
+    Guild,
+    Channel,
+}
+
+impl BucketScope {
+    /// Whether the uses are counted inside one guild, only then can a guild have its own limit.
+    /// A global or user bucket's uses are shared with other guilds
+    fn is_per_guild(&self) -> bool {
+        matches!(self, BucketScope::Guild | BucketScope::Channel)
+    }
+
+    fn id(&self, user_id: UserId, channel_id: ChannelId, guild_id: Option<GuildId>) -> u64 {
+        match self {
+            BucketScope::Global => 0,
+            BucketScope::User => user_id.0,
+            BucketScope::Guild => guild_id.map_or(channel_id.0, |guild_id| guild_id.0),
+            BucketScope::Channel => channel_id.0,
+        }
+    }
+}
+
+/// A rate limit in the config, commands use it by its name
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Bucket {
+    #[serde(default)]
+    scope: BucketScope,
+    limit: u32,
+    time_span: u64,
+    #[serde(default)]
+    delay: u64,
+    #[serde(default)]
+    await_ratelimits: u32,
+}
+
+impl Bucket {
+    pub fn validate(&self, field: &str, problems: &mut Vec<ConfigProblem>) {
+        if self.limit == 0 {
+            problems.push(ConfigProblem::new(
+                format!("{}.limit", field),
+                self.limit,
+                "Nobody could use the commands in this bucket",
+                "Set it to at least 1",
+            ));
+        }
+        if self.time_span == 0 && self.delay == 0 {
+            problems.push(ConfigProblem::new(
+                format!("{}.time_span", field),
+                self.time_span,
+                "It wouldn't limit anything without a time span or a delay",
+                "Set it to how many seconds the limit is for, like 600",
+            ));
+        }
+    }
+}
+
+/// The buckets the commands use, the config can change them or add more
+pub fn default_buckets() -> HashMap<String, Bucket> {
+    vec![
+        (
+            "general".to_string(),
+            Bucket {
+                scope: BucketScope::Channel,
+                limit: 10,
+                time_span: 600,
+                delay: 0,
+                await_ratelimits: 1,
+            },
+        ),
+        (
+            "expensive".to_string(),
+            Bucket {
+                scope: BucketScope::Guild,
+                limit: 10,
+                time_span: 3600,
+                delay: 0,
+                await_ratelimits: 1,
+            },
+        ),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// What's happening with a bucket right now, the uses themselves are in the database so they
+/// survive restarts
+#[derive(Default)]
+struct Usage {
+    waiting: u32,
+    is_notified: bool,
+}
+
+/// The usage per bucket name and the ID of what its scope is
+static USAGES: Lazy<DashMap<(String, u64), Usage>> = Lazy::new(DashMap::new);
+/// The limit and time span per guild and bucket name, for the guilds that need different limits
+static OVERRIDES: Lazy<DashMap<(GuildId, String), (u32, u64)>> = Lazy::new(DashMap::new);
+static PRUNING: AtomicBool = AtomicBool::new(false);
+
+pub async fn load_bucket_overrides(db: &AnyPool) {
+    let rows = query("SELECT guild_id, bucket, uses, time_span FROM bucket_overrides")
+        .fetch_all(db)
+        .await
+        .expect("Couldn't load the bucket overrides");
+
+    for row in rows.iter() {
+        let guild_id: i64 = row
+            .try_get(0)
+            .expect("Couldn't get a guild_id from bucket_overrides");
+        let uses: i64 = row
+            .try_get(2)
+            .expect("Couldn't get uses from bucket_overrides");
+        let time_span: i64 = row
+            .try_get(3)
+            .expect("Couldn't get a time_span from bucket_overrides");
+        OVERRIDES.insert(
+            (
+                GuildId(guild_id as u64),
+                row.try_get(1)
+                    .expect("Couldn't get a bucket from bucket_overrides"),
+            ),
+            (uses as u32, time_span as u64),
+        );
+    }
+}
+
+pub fn forget_bucket_overrides(guild_id: GuildId) {
+    OVERRIDES.retain(|(id, _), _| *id != guild_id);
+}
+
+/// Deletes the uses that don't count for any bucket anymore every `PRUNE_SECONDS`, only the first
+/// call does anything
+pub fn prune_uses_periodically(ctx: &Context) {
+    if PRUNING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(PRUNE_SECONDS));
+        loop {
+            interval.tick().await;
+            let db = match ctx.data.read().await.get::<DatabaseKey>().cloned() {
+                Some(db) => db,
+                None => {
+                    log(&ctx, "Couldn't get the database to prune the rate limits").await;
+                    continue;
+                }
+            };
+            if let Err(err) = query("DELETE FROM rate_limit_uses WHERE expires_at < $1")
+                .bind(Utc::now().timestamp_millis())
+                .execute(&db)
+                .await
+            {
+                log(&ctx, format!("Couldn't prune the rate limits: {}", err)).await;
+            }
+        }
+    });
+}
+
+/// Counts a use if the bucket allows it, otherwise returns how long to wait until it does
+///
+/// The uses in the last `time_span` are counted, so it's a sliding window. The bucket and scope
+/// are locked until the use is written so processes sharing the database can't both take the last
+/// one
+async fn take(
+    db: &AnyPool,
+    name: &str,
+    bucket: &Bucket,
+    guild_id: Option<GuildId>,
+    scope_id: u64,
+) -> sqlx::Result<Option<Duration>> {
+    let (limit, time_span) = guild_id
+        .filter(|_| bucket.scope.is_per_guild())
+        .and_then(|guild_id| OVERRIDES.get(&(guild_id, name.to_string())))
+        .map_or((bucket.limit, bucket.time_span), |limits| *limits);
+    let time_span = time_span as i64 * 1000;
+    let delay = bucket.delay as i64 * 1000;
+
+    let mut transaction = db.begin().await?;
+    match db.any_kind() {
+        // Released when the transaction ends
+        AnyKind::Postgres => {
+            query("SELECT pg_advisory_xact_lock(hashtextextended($1, $2))")
+                .bind(name)
+                .bind(scope_id as i64)
+                .execute(&mut transaction)
+                .await?;
+        }
+        // Writing first takes the database's write lock, so the others wait for it instead of
+        // failing when they try to write after counting
+        AnyKind::Sqlite => {
+            query("DELETE FROM rate_limit_uses WHERE bucket = $1 AND scope_id = $2 AND expires_at < $3")
+                .bind(name)
+                .bind(scope_id as i64)
+                .bind(Utc::now().timestamp_millis())
+                .execute(&mut transaction)
+                .await?;
+        }
+    }
+    // After waiting for the lock, otherwise the use written meanwhile would look like it's from
+    // the future
+    let now = Utc::now().timestamp_millis();
+    let row = query(
+        "SELECT COUNT(CASE WHEN used_at > $3 THEN 1 END),
+            MIN(CASE WHEN used_at > $3 THEN used_at END),
+            MAX(used_at)
+        FROM rate_limit_uses WHERE bucket = $1 AND scope_id = $2 AND used_at > $4",
+    )
+    .bind(name)
+    .bind(scope_id as i64)
+    .bind(now - time_span)
+    .bind(now - time_span.max(delay))
+    .fetch_one(&mut transaction)
+    .await?;
+    let uses: i64 = row.try_get(0)?;
+    let first: Option<i64> = row.try_get(1)?;
+    let last: Option<i64> = row.try_get(2)?;
+
+    let mut wait = 0;
+    if uses >= limit as i64 {
+        if let Some(first) = first {
+            wait = first + time_span - now;
+        }
+    }
+    if let Some(last) = last.filter(|_| delay > 0) {
+        wait = wait.max(last + delay - now);
+    }
+    if wait > 0 {
+        return Ok(Some(Duration::from_millis(wait as u64)));
+    }
+
+    query(
+        "INSERT INTO rate_limit_uses (bucket, scope_id, used_at, expires_at) VALUES($1, $2, $3, $4)",
+    )
+    .bind(name)
+    .bind(scope_id as i64)
+    .bind(now)
+    .bind(now + time_span.max(delay))
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    if let Some(mut usage) = USAGES.get_mut(&(name.to_string(), scope_id)) {
+        usage.is_notified = false;
+    }
+    Ok(None)
+}
+
+/// Waits for the bucket if it can, `false` if too many are waiting already
+fn start_waiting(name: &str, bucket: &Bucket, scope_id: u64) -> bool {
+    let mut usage = USAGES.entry((name.to_string(), scope_id)).or_default();
+    if usage.waiting < bucket.await_ratelimits {
+        usage.waiting += 1;
+        true
+    } else {
+        false
+    }
+}
+
+fn stop_waiting(name: &str, scope_id: u64) {
+    if let Some(mut usage) = USAGES.get_mut(&(name.to_string(), scope_id)) {
+        usage.waiting = usage.waiting.saturating_sub(1);
+    }
+}
+
+/// `true` the first time it's called since the bucket last let a command through
+fn should_notify(name: &str, scope_id: u64) -> bool {
+    !std::mem::replace(
+        &mut USAGES
+            .entry((name.to_string(), scope_id))
+            .or_default()
+            .is_notified,
+        true,
+    )
+}
+
+/// Takes a use from the bucket for a command the user used in the channel, waiting for it if the
+/// bucket lets them. The message the command is from gets a reaction while it waits
+///
+/// The error is what to tell the user the first time they're limited, after that it's only logged
+pub async fn take_bucket(
+    ctx: &Context,
+    name: &str,
+    user_id: UserId,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    msg: Option<&Message>,
+) -> Result<(), Reason> {
+    let bucket = match BotConfig::get().and_then(|config| config.buckets().get(name).cloned()) {
+        Some(bucket) => bucket,
+        None => {
+            log(
+                ctx,
+                format!(
+                    "A command uses the bucket {} but it isn't in the config",
+                    name
+                ),
+            )
+            .await;
+            return Ok(());
+        }
+    };
+    let db = match ctx.data.read().await.get::<DatabaseKey>().cloned() {
+        Some(db) => db,
+        None => {
+            log(ctx, "Couldn't get the database for the rate limits").await;
+            return Ok(());
+        }
+    };
+    let scope_id = bucket.scope.id(user_id, channel_id, guild_id);
+
+    let mut is_waiting = false;
+    let result = loop {
+        let wait = match take(&db, name, &bucket, guild_id, scope_id).await {
+            Ok(Some(wait)) => wait,
+            Ok(None) => break Ok(()),
+            // Better to let it through than to block every command when the database is down
+            Err(err) => {
+                log(
+                    ctx,
+                    format!("Couldn't check the rate limit {}: {}", name, err),
+                )
+                .await;
+                break Ok(());
+            }
+        };
+
+        if !is_waiting && start_waiting(name, &bucket, scope_id) {
+            is_waiting = true;
+            if let Some(msg) = msg {
+                cmd_error::delay_action(ctx, msg).await;
+            }
+        }
+        if is_waiting {
+            tokio::time::sleep(wait).await;
+            continue;
+        }
+
+        break if should_notify(name, scope_id) {
+            Err(Reason::User(format!(
+                "Calm down and try again in {} seconds please",
+                wait.as_secs().max(1)
+            )))
+        } else {
+            Err(Reason::Log(format!("Still rate limited by {}", name)))
+        };
+    };
+
+    if is_waiting {
+        stop_waiting(name, scope_id);
+    }
+    result
+}
+
+#[check]
+#[name = "rate_limit"]
+#[display_in_help(false)]
+#[check_in_help(false)]
+pub async fn rate_limit(
+    ctx: &Context,
+    msg: &Message,
+    _args: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    match options.bucket {
+        Some(name) => {
+            take_bucket(
+                ctx,
+                name,
+                msg.author.id,
+                msg.channel_id,
+                msg.guild_id,
+                Some(msg),
+            )
+            .await
+        }
+        None => Ok(()),
+    }
+}
+
+#[command("bucket")]
+#[aliases("buckets", "ratelimits", "rate_limits", "rate-limits")]
+#[sub_commands(cmd_bucket_set, cmd_bucket_reset)]
+#[owners_only]
+#[help_available(false)]
+#[description = "See the rate limits and the guilds that have their own"]
+#[usage = "[set or reset]"]
+async fn cmd_bucket(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+
+    match BotConfig::get() {
+        Some(config) => {
+            let mut names: Vec<&String> = config.buckets().keys().collect();
+            names.sort();
+            for name in names {
+                let bucket = &config.buckets()[name];
+                let mut overrides: Vec<String> = OVERRIDES
+                    .iter()
+                    .filter(|entry| &entry.key().1 == name)
+                    .map(|entry| {
+                        let (limit, time_span) = entry.value();
+                        format!(
+                            "{}: {} uses per {} seconds",
+                            entry.key().0,
+                            limit,
+                            time_span
+                        )
+                    })
+                    .collect();
+                overrides.sort();
+
+                embed.field(
+                    name,
+                    format!(
+                        "{} uses per {} seconds per {:?}\n{}",
+                        bucket.limit,
+                        bucket.time_span,
+                        bucket.scope,
+                        overrides.join("\n")
+                    ),
+                    false,
+                );
+            }
+            embed.title("My rate limits");
+            send_embed(ctx, msg, false, embed).await;
+        }
+        None => {
+            log(ctx, "Couldn't get BotConfig for the bucket command").await;
+            embed.title("Uh, I forgot my own rate limits somehow..");
+            send_embed(ctx, msg, true, embed).await;
+        }
+    }
+    Ok(())
+}
+
+#[command("set")]
+#[owners_only]
+#[description = "Give a guild its own limit for a bucket"]
+#[usage = "[guild ID] [bucket] [uses] [seconds]"]
+#[example = "752582273706098699 general 30 600"]
+async fn cmd_bucket_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let guild_id = args.single::<u64>().ok().map(GuildId);
+    let name = args.single::<String>().ok();
+    let limit = args.single::<u32>().ok();
+    let time_span = args.single::<u64>().ok();
+
+    let (guild_id, name, limit, time_span) = match (guild_id, name, limit, time_span) {
+        (Some(guild_id), Some(name), Some(limit), Some(time_span))
+            if limit > 0 && time_span > 0 =>
+        {
+            (guild_id, name, limit, time_span)
+        }
+        _ => {
+            embed
+                .title("I need the guild's ID, the bucket's name, how many uses and in how many seconds")
+                .description("Like `bucket set 752582273706098699 general 30 600`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+    let scope = match BotConfig::get().and_then(|config| config.buckets().get(&name).cloned()) {
+        Some(bucket) => bucket.scope,
+        None => {
+            embed.title(format!("There's no bucket named `{}`", name));
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+    if !scope.is_per_guild() {
+        embed
+            .title(format!("`{}` counts the uses per {:?}", name, scope))
+            .description(
+                "Only the buckets per guild or channel can have their own limit in a guild",
+            );
+        send_embed(ctx, msg, true, embed).await;
+        return Ok(());
+    }
+
+    let result = match db(ctx, msg, "bucket").await {
+        Some(db) => query(
+            "INSERT INTO bucket_overrides (guild_id, bucket, uses, time_span) VALUES($1, $2, $3, $4)
+            ON CONFLICT (guild_id, bucket) DO UPDATE SET uses = excluded.uses, time_span = excluded.time_span",
+        )
+        .bind(guild_id.0 as i64)
+        .bind(&name)
+        .bind(limit as i64)
+        .bind(time_span as i64)
+        .execute(&db)
+        .await
+        .map(|_| ()),
+        None => return Ok(()),
+    };
+
+    match result {
+        Ok(_) => {
+            OVERRIDES.insert((guild_id, name.clone()), (limit, time_span));
+            embed.description(format!(
+                "Done! {} can use `{}` {} times per {} seconds now",
+                guild_id, name, limit, time_span
+            ));
+            send_embed(ctx, msg, false, embed).await;
+        }
+        Err(err) => {
+            log(ctx, format!("Couldn't save a bucket override: {}", err)).await;
+            embed
+                .title("Ugh, I couldn't write that down..")
+                .description(err);
+            send_embed(ctx, msg, true, embed).await;
+        }
+    }
+    Ok(())
+}
+
+#[command("reset")]
+#[aliases("remove")]
+#[owners_only]
+#[description = "Make a guild use the bucket's normal limit again"]
+#[usage = "[guild ID] [bucket]"]
+#[example = "752582273706098699 general"]
+async fn cmd_bucket_reset(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let (guild_id, name) = match (args.single::<u64>(), args.single::<String>()) {
+        (Ok(guild_id), Ok(name)) => (GuildId(guild_id), name),
+        _ => {
+            embed
+                .title("I need the guild's ID and the bucket's name")
+                .description("Like `bucket reset 752582273706098699 general`");
+            send_embed(ctx, msg, true, embed).await;
+            return Ok(());
+        }
+    };
+
+    let result = match db(ctx, msg, "bucket").await {
+        Some(db) => query("DELETE FROM bucket_overrides WHERE guild_id = $1 AND bucket = $2")
+            .bind(guild_id.0 as i64)
+            .bind(&name)
+            .execute(&db)
+            .await
+            .map(|_| ()),
+        None => return Ok(()),
+    };
+
+    match result {
+        Ok(_) => {
+            OVERRIDES.remove(&(guild_id, name.clone()));
+            embed.description(format!(
+                "Done! {} uses the normal limit for `{}` again",
+                guild_id, name
+            ));
+            send_embed(ctx, msg, false, embed).await;
+        }
+        Err(err) => {
+            log(ctx, format!("Couldn't delete a bucket override: {}", err)).await;
+            embed.title("Ugh, I couldn't erase that..").description(err);
+            send_embed(ctx, msg, true, embed).await;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use sqlx::any::AnyPoolOptions;
+
+    use super::*;
+    use crate::migrations::migrate;
+
+    const BUCKET: Bucket = Bucket {
+        scope: BucketScope::Channel,
+        limit: 5,
+        time_span: 600,
+        delay: 0,
+        await_ratelimits: 0,
+    };
+
+    /// Takes from the bucket as many times at once from each pool and returns how many went
+    /// through, the pools are like separate processes sharing the database
+    async fn take_at_once(pools: &[AnyPool], times: usize, name: &str) -> usize {
+        let takes: Vec<_> = pools
+            .iter()
+            .flat_map(|db| std::iter::repeat_n(db.clone(), times))
+            .map(|db| {
+                let name = name.to_string();
+                tokio::spawn(async move { take(&db, &name, &BUCKET, None, 1).await })
+            })
+            .collect();
+
+        let mut taken = 0;
+        for result in takes {
+            match result.await.expect("A take panicked") {
+                Ok(None) => taken += 1,
+                Ok(Some(_)) => {}
+                Err(err) => panic!("Couldn't take from the bucket: {}", err),
+            }
+        }
+        taken
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn takes_only_the_limit_at_once_with_sqlite() {
+        let path =
+            env::temp_dir().join(format!("discord-search-buckets-{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        let mut pools = Vec::new();
+        for _ in 0..4 {
+            pools.push(
+                AnyPoolOptions::new()
+                    .max_connections(5)
+                    .connect(&url)
+                    .await
+                    .expect("Couldn't open the database"),
+            );
+        }
+        migrate(&pools[0]).await;
+
+        let taken = take_at_once(&pools, 20, "general").await;
+        for db in pools {
+            db.close().await;
+        }
+        let _ = fs::remove_file(&path);
+        assert_eq!(taken, BUCKET.limit as usize);
+    }
+
+    #[tokio::test]
+    async fn uses_the_guilds_limit_only_for_buckets_per_guild() {
+        let db = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("Couldn't open the database");
+        migrate(&db).await;
+
+        let guild_id = GuildId(81384788765712384);
+        let cases = [
+            (BucketScope::Guild, 2),
+            (BucketScope::Channel, 2),
+            (BucketScope::User, BUCKET.limit),
+            (BucketScope::Global, BUCKET.limit),
+        ];
+        for (scope, limit) in cases.iter() {
+            let name = format!("overridden-{:?}", scope);
+            let bucket = Bucket {
+                scope: *scope,
+                ..BUCKET
+            };
+            OVERRIDES.insert((guild_id, name.clone()), (2, 600));
+
+            let mut taken = 0;
+            for _ in 0..10 {
+                if take(&db, &name, &bucket, Some(guild_id), 1)
+                    .await
+                    .expect("Couldn't take from the bucket")
+                    .is_none()
+                {
+                    taken += 1;
+                }
+            }
+            assert_eq!(taken, *limit, "{:?}", scope);
+        }
+    }
+
+    #[cfg(feature = "postgres-tests")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn takes_only_the_limit_at_once_with_postgres() {
+        use crate::migrations::tests::postgres::{connect, wipe, DATABASE};
+
+        let _database = DATABASE.lock().await;
+        let db = connect().await;
+        wipe(&db).await;
+        migrate(&db).await;
+        let mut pools = vec![db.clone()];
+        for _ in 0..3 {
+            pools.push(connect().await);
+        }
+
+        let taken = take_at_once(&pools, 20, "general").await;
+        wipe(&db).await;
+        assert_eq!(taken, BUCKET.limit as usize);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashSet;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    framework::standard::{
+        macros::{check, command},
+        Args, CommandOptions, CommandResult, Reason,
+    },
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId},
+    },
+};
+use sqlx::{query, AnyPool, Row};
+
+use crate::{cmd_search, cmd_site::has_guild_site, guild_and_db, log, send_embed};
+
+const IN_DMS: &str = "We have to be in a guild to change its channels, no?";
+
+#[derive(Clone, Default)]
+struct ChannelSettings {
+    guild_id: GuildId,
+    prefix: Option<String>,
+    disabled_cmds: HashSet<String>,
+}
+
+static CHANNEL_SETTINGS: Lazy<DashMap<ChannelId, ChannelSettings>> = Lazy::new(DashMap::new);
+
+pub async fn load_channel_settings(db: &AnyPool) {
+    let rows = query("SELECT channel_id, guild_id, prefix, disabled_cmds FROM channel_settings")
+        .fetch_all(db)
+        .await
+        .expect("Couldn't load the channel settings");
+
+    for row in rows.iter() {
+        let channel_id: i64 = row
+            .try_get(0)
+            .expect("Couldn't get a channel_id from channel_settings");
+        let guild_id: i64 = row
+            .try_get(1)
+            .expect("Couldn't get a guild_id from channel_settings");
+        let disabled_cmds: String = row
+            .try_get(3)
+            .expect("Couldn't get disabled_cmds from channel_settings");
+        CHANNEL_SETTINGS.insert(
+            ChannelId(channel_id as u64),
+            ChannelSettings {
+                guild_id: GuildId(guild_id as u64),
+                prefix: row
+                    .try_get(2)
+                    .expect("Couldn't get a prefix from channel_settings"),
+                disabled_cmds: disabled_cmds.split_whitespace().map(String::from).collect(),
+            },
+        );
+    }
+}
+
+pub fn forget_channel_settings(guild_id: GuildId) {
+    CHANNEL_SETTINGS.retain(|_, settings| settings.guild_id != guild_id);
+}
+
+/// The prefix to use in the channel instead of the guild's prefixes, if it has one
+pub fn channel_prefix(channel_id: ChannelId) -> Option<String> {
+    CHANNEL_SETTINGS.get(&channel_id)?.prefix.clone()
+}
+
+pub fn is_cmd_disabled(channel_id: ChannelId, name: &str) -> bool {
+    CHANNEL_SETTINGS
+        .get(&channel_id)
+        .is_some_and(|settings| settings.disabled_cmds.contains(&name.to_lowercase()))
+}
+
+#[check]
+#[display_in_help(false)]
+#[check_in_help(false)]
+async fn enabled_here(
+    _ctx: &Context,
+    msg: &Message,
+    _args: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    match options.names.first() {
+        Some(name) if is_cmd_disabled(msg.channel_id, name) => Err(Reason::User(
+            "The admins turned this command off in this channel".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[command("channel")]
+#[aliases("channels", "channel_settings", "channel-settings")]
+#[sub_commands(
+    cmd_channel_prefix,
+    cmd_channel_disable,
+    cmd_channel_enable,
+    cmd_channel_show,
+    cmd_channel_reset
+)]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[description = "Change how I work in this channel, like not needing a prefix in your bot spam channel\nYou can change the prefix or turn the search commands off or on here"]
+#[usage = "[prefix, disable, enable, show or reset]"]
+#[example = "show"]
+async fn cmd_channel(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("Do you want to change the prefix, disable, enable, show or reset the settings?")
+        .description("Type `help channel` if you don't know how");
+    send_embed(ctx, msg, true, embed).await;
+    Ok(())
+}
+
+#[command("prefix")]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Change the prefix I'll use in this channel instead of the server's prefixes\nLeave it empty if you don't want one and type `reset` to use the server's prefixes again"]
+#[usage = "[your prefix or reset]"]
+#[example = "reset"]
+async fn cmd_channel_prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let prefix = args.rest().trim();
+
+    if prefix.chars().count() > 10 {
+        let mut embed = CreateEmbed::default();
+        embed
+            .title("Your prefix can't be longer than 10 characters")
+            .description("Why would you want it that long anyway..");
+        send_embed(ctx, msg, true, embed).await;
+        return Ok(());
+    }
+
+    let description = if prefix.eq_ignore_ascii_case("reset") {
+        "Done! I'll use the server's prefixes here again".to_string()
+    } else if prefix.is_empty() {
+        "Yay! I don't even need a prefix in this channel anymore".to_string()
+    } else {
+        format!("Voila! My prefix in this channel is now `{}`", prefix)
+    };
+    let prefix = if prefix.eq_ignore_ascii_case("reset") {
+        None
+    } else {
+        Some(prefix.to_string())
+    };
+
+    update_settings(ctx, msg, description, |settings| settings.prefix = prefix).await;
+    Ok(())
+}
+
+#[command("disable")]
+#[aliases("off")]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Turn a search command off in this channel"]
+#[usage = "[command name]"]
+#[example = "urban"]
+async fn cmd_channel_disable(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if let Some(name) = search_cmd_name(ctx, msg, args.rest()).await {
+        let description = format!("Done! `{}` is turned off in this channel", name);
+        update_settings(ctx, msg, description, |settings| {
+            settings.disabled_cmds.insert(name);
+        })
+        .await;
+    }
+    Ok(())
+}
+
+#[command("enable")]
+#[aliases("on")]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Turn a search command you turned off back on in this channel"]
+#[usage = "[command name]"]
+#[example = "urban"]
+async fn cmd_channel_enable(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if let Some(name) = search_cmd_name(ctx, msg, args.rest()).await {
+        let description = format!("Yay! `{}` works in this channel again", name);
+        update_settings(ctx, msg, description, |settings| {
+            settings.disabled_cmds.remove(&name);
+        })
+        .await;
+    }
+    Ok(())
+}
+
+#[command("show")]
+#[aliases("list", "ls", "settings")]
+#[only_in("guilds")]
+#[bucket = "general"]
+#[description = "See how I work in this channel"]
+async fn cmd_channel_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let settings = CHANNEL_SETTINGS
+        .get(&msg.channel_id)
+        .map(|settings| settings.clone())
+        .unwrap_or_default();
+
+    let mut disabled_cmds = settings
+        .disabled_cmds
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<String>>();
+    disabled_cmds.sort();
+
+    embed
+        .title("My settings in this channel")
+        .field(
+            "Prefix",
+            match settings.prefix {
+                Some(prefix) if prefix.is_empty() => "*No prefix at all*".to_string(),
+                Some(prefix) => format!("`{}`", prefix),
+                None => "*The server's prefixes*".to_string(),
+            },
+            false,
+        )
+        .field(
+            "Turned off",
+            if disabled_cmds.is_empty() {
+                "*Nothing*".to_string()
+            } else {
+                disabled_cmds.join(", ")
+            },
+            false,
+        );
+
+    send_embed(ctx, msg, false, embed).await;
+    Ok(())
+}
+
+#[command("reset")]
+#[aliases("clear")]
+#[required_permissions("MANAGE_CHANNELS")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Go back to using the server's prefixes and turn every search command back on in this channel"]
+async fn cmd_channel_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    update_settings(
+        ctx,
+        msg,
+        "Done! This channel is just like the others now".to_string(),
+        |settings| *settings = ChannelSettings::default(),
+    )
+    .await;
+    Ok(())
+}
+
+async fn search_cmd_name(ctx: &Context, msg: &Message, name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    if let Some(name) = cmd_search::site_name(&name) {
+        return Some(name.to_string());
+    }
+    if msg
+        .guild_id
+        .is_some_and(|guild_id| has_guild_site(guild_id, &name))
+    {
+        return Some(name);
+    }
+
+    let mut embed = CreateEmbed::default();
+    if name.is_empty() {
+        embed.title("Which command though?");
+    } else {
+        embed.title(format!("I don't have a search command named `{}`", name));
+    }
+    embed.description("You can only turn the search commands and the sites added here off or on");
+    send_embed(ctx, msg, true, embed).await;
+    None
+}
+
+async fn update_settings(
+    ctx: &Context,
+    msg: &Message,
+    description: String,
+    update: impl FnOnce(&mut ChannelSettings),
+) {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+    if let Some((guild_id, db)) =
+        guild_and_db(ctx, msg.guild_id, &mut embed, "channel", IN_DMS).await
+    {
+        let mut settings = CHANNEL_SETTINGS
+            .get(&msg.channel_id)
+            .map(|settings| settings.clone())
+            .unwrap_or_default();
+        update(&mut settings);
+        settings.guild_id = guild_id;
+
+        if let Err(err) = save_settings(&db, msg.channel_id, &settings).await {
+            log(ctx, format!("Couldn't write to channel_settings: {}", err)).await;
+            embed
+                .title("Ugh, I couldn't write that down..")
+                .description("I just let my developer know, until then you could just try again");
+        } else {
+            if settings.prefix.is_none() && settings.disabled_cmds.is_empty() {
+                CHANNEL_SETTINGS.remove(&msg.channel_id);
+            } else {
+                CHANNEL_SETTINGS.insert(msg.channel_id, settings);
+            }
+            is_error = false;
+            embed.description(description);
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+}
+
+async fn save_settings(
+    db: &AnyPool,
+    channel_id: ChannelId,
+    settings: &ChannelSettings,
+) -> sqlx::Result<()> {
+    if settings.prefix.is_none() && settings.disabled_cmds.is_empty() {
+        query("DELETE FROM channel_settings WHERE channel_id = $1")
+            .bind(channel_id.0 as i64)
+            .execute(db)
+            .await?;
+        return Ok(());
+    }
+
+    query(
+        "INSERT INTO channel_settings (channel_id, guild_id, prefix, disabled_cmds)
+        VALUES($1, $2, $3, $4)
+        ON CONFLICT (channel_id) DO UPDATE
+        SET guild_id = excluded.guild_id, prefix = excluded.prefix, disabled_cmds = excluded.disabled_cmds;",
+    )
+    .bind(channel_id.0 as i64)
+    .bind(settings.guild_id.0 as i64)
+    .bind(&settings.prefix)
+    .bind(
+        settings
+            .disabled_cmds
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<&str>>()
+            .join(" "),
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -5,7 +5,7 @@ use serenity::{
     model::channel::Message,
 };
 
-use crate::send_embed;
+use crate::{cmd_block::NOT_BLOCKED_CHECK, cmd_bucket::RATE_LIMIT_CHECK, send_embed};
 
 #[hook]
 pub async fn handle(ctx: &Context, msg: &Message, error: DispatchError) {
@@ -17,6 +17,14 @@ pub async fn handle(ctx: &Context, msg: &Message, error: DispatchError) {
 
     let mut embed = CreateEmbed::default();
     embed.description(match error {
+        DispatchError::CheckFailed(info, reason)
+            if info == RATE_LIMIT_CHECK.name || info == NOT_BLOCKED_CHECK.name =>
+        {
+            match reason {
+                Reason::User(reason) => reason,
+                _ => return,
+            }
+        }
         DispatchError::CheckFailed(info, reason) => {
             if let Reason::User(reason) = reason {
                 format!("Seems like you don't pass the check.. {}\n{}", reason, info)
@@ -43,16 +51,6 @@ pub async fn handle(ctx: &Context, msg: &Message, error: DispatchError) {
             "This command can't take more than {} arguments™ but you gave {}..",
             max, given
         ),
-        DispatchError::BlockedUser => {
-            "Oops, you're blocked to use this command for some reason..".to_string()
-        }
-        DispatchError::BlockedGuild => {
-            "Oops, the guild or its owner is blocked to use this command for some reason.."
-                .to_string()
-        }
-        DispatchError::BlockedChannel => {
-            "Oops, the channel is blocked to use this command for some reason..".to_string()
-        }
         DispatchError::OnlyForDM => "You can only use this command in my DMs 😳".to_string(),
         DispatchError::OnlyForGuilds => "You can only use this command in a guild 😳".to_string(),
         DispatchError::OnlyForOwners => "This command is dedicated to my master".to_string(),
@@ -72,6 +70,6 @@ pub async fn delay_action(ctx: &Context, msg: &Message) {
         embed.title("I was going to react with 😤 to show my frustration of you being so impatient, but I couldn't so I'm even more frustrated now 😤")
         .description(err);
 
-        send_embed(ctx, msg, true, embed).await
+        send_embed(ctx, msg, true, embed).await;
     };
 }
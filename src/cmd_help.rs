@@ -1,13 +1,26 @@
 use std::collections::HashSet;
 
+use dashmap::DashMap;
+use once_cell::sync::{Lazy, OnceCell};
 use serenity::{
+    builder::CreateEmbed,
     client::Context,
     framework::standard::{
-        help_commands, macros::help, Args, CommandGroup, CommandResult, HelpOptions,
+        help_commands, macros::help, Args, CommandGroup, CommandResult, HelpOptions, Reason,
     },
     model::{channel::Message, id::UserId},
 };
 
+use crate::{cmd_block::blocked_reason, cmd_prefix::group_prefix, send_embed};
+
+/// The most tips with a group prefix in them to keep, they're leaked since the help options need
+/// `&'static str` and the guilds choose their group prefixes
+const MAX_COMMAND_TIPS: usize = 64;
+
+static COMMAND_TIPS: Lazy<DashMap<String, &'static str>> = Lazy::new(DashMap::new);
+/// The tip for the group prefixes that didn't fit in `COMMAND_TIPS`
+static GROUP_PREFIX_TIP: OnceCell<&'static str> = OnceCell::new();
+
 #[help("help", "commands", "cmds")]
 #[suggestion_text = "**Maybe you meant one of these:**\n{}"]
 #[max_levenshtein_distance(3)]
@@ -24,7 +37,7 @@ use serenity::{
 #[dm_only_text = "My DMs only 😳"]
 #[guild_only_text = "Guilds only"]
 #[dm_and_guild_text = "Both guilds and DMs"]
-#[individual_command_tip = "Want me to explain a command? Type `help [command name]`\nYou can use `.` as the prefix if the command isn't in `General Stuff`"]
+#[individual_command_tip = "Want me to explain a command? Type `help [command name]`"]
 #[strikethrough_commands_tip_in_dm = ""]
 #[strikethrough_commands_tip_in_guild = ""]
 #[lacking_role = "Nothing"]
@@ -42,6 +55,48 @@ async fn cmd_help(
     groups: &[&'static CommandGroup],
     owners: HashSet<UserId>,
 ) -> CommandResult {
-    help_commands::with_embeds(context, msg, args, help_options, groups, owners).await;
+    // The help command can't have checks, so it's the same as the not_blocked check here
+    match blocked_reason(msg.author.id, msg.guild_id, msg.channel_id) {
+        Some(Reason::User(text)) => {
+            let mut embed = CreateEmbed::default();
+            embed.description(text);
+            send_embed(context, msg, true, embed).await;
+            return Ok(());
+        }
+        Some(_) => return Ok(()),
+        None => {}
+    }
+
+    let group_prefix = group_prefix(msg.guild_id);
+    let mut help_options = help_options.clone();
+    if !group_prefix.is_empty() {
+        let tip = help_options.individual_command_tip;
+        let cached = COMMAND_TIPS.get(&group_prefix).map(|group_tip| *group_tip);
+        help_options.individual_command_tip = match cached {
+            Some(group_tip) => group_tip,
+            None if COMMAND_TIPS.len() < MAX_COMMAND_TIPS => *COMMAND_TIPS
+                .entry(group_prefix.clone())
+                .or_insert_with(|| {
+                    Box::leak(
+                        format!(
+                            "{}\nYou can use `{}` as the prefix if the command isn't in `General Stuff`",
+                            tip, group_prefix
+                        )
+                        .into_boxed_str(),
+                    )
+                }),
+            None => *GROUP_PREFIX_TIP.get_or_init(|| {
+                Box::leak(
+                    format!(
+                        "{}\nType `prefix group` to see the prefix for the commands that aren't in `General Stuff`",
+                        tip
+                    )
+                    .into_boxed_str(),
+                )
+            }),
+        };
+    }
+
+    help_commands::with_embeds(context, msg, args, &help_options, groups, owners).await;
     Ok(())
 }
@@ -16,6 +16,12 @@ use crate::{
 #[bucket = "general"]
 #[description = "How you can add me to your server, contact my owner, find my GitHub page etc."]
 async fn cmd_info(ctx: &Context, msg: &Message) -> CommandResult {
+    let (embed, is_error) = info_embed(ctx).await;
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+pub async fn info_embed(ctx: &Context) -> (CreateEmbed, bool) {
     let mut embed = CreateEmbed::default();
     embed.footer(|f| {
         f.text("I act weirdly? Want me to speak another language? Anything else? You can friend and DM my owner anytime for any feedback you have!")
@@ -25,7 +31,7 @@ async fn cmd_info(ctx: &Context, msg: &Message) -> CommandResult {
     match BotInfo::get() {
         Some(info) => {
             embed
-                .description(&info.description())
+                .description(info.description())
                 .field("Made by:", info.owner().mention(), true);
         }
         None => {
@@ -39,8 +45,8 @@ async fn cmd_info(ctx: &Context, msg: &Message) -> CommandResult {
         Some(config) => {
             embed
                 .title("Want me in your server? Click here then!")
-                .url(&config.invite())
-                .field("on GitHub:", &config.github(), true);
+                .url(config.invite())
+                .field("on GitHub:", config.github(), true);
         }
         None => {
             log(ctx, "Couldn't get BotConfig for the `info` command").await;
@@ -48,6 +54,5 @@ async fn cmd_info(ctx: &Context, msg: &Message) -> CommandResult {
             is_error = true
         }
     };
-    send_embed(ctx, msg, is_error, embed).await;
-    Ok(())
+    (embed, is_error)
 }
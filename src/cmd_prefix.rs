@@ -1,18 +1,91 @@
+use std::cmp::Reverse;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
 use serenity::{
     builder::CreateEmbed,
     client::Context,
     framework::standard::{macros::command, Args, CommandResult},
-    model::channel::Message,
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId},
+    },
 };
-use sqlx::{query, Row};
+use sqlx::{query, AnyPool, Row};
 
 use crate::{
-    globals::{CmdInfo, SqlitePoolKey},
-    log, send_embed,
+    cmd_channel::channel_prefix,
+    cmd_site::has_guild_site,
+    globals::{BotConfig, CmdInfo},
+    guild_and_db, log, send_embed,
 };
 
+const IN_DMS: &str = "We have to be in a guild to set the prefix for a guild, no?";
+
+static PREFIXES: Lazy<DashMap<GuildId, Vec<String>>> = Lazy::new(DashMap::new);
+static GROUP_PREFIXES: Lazy<DashMap<GuildId, String>> = Lazy::new(DashMap::new);
+
+pub async fn load_prefixes(db: &AnyPool) {
+    let rows = query("SELECT guild_id, prefix FROM guild_prefixes")
+        .fetch_all(db)
+        .await
+        .expect("Couldn't load the prefixes");
+
+    for row in rows.iter() {
+        let guild_id: i64 = row
+            .try_get(0)
+            .expect("Couldn't get a guild_id from guild_prefixes");
+        let prefix: String = row
+            .try_get(1)
+            .expect("Couldn't get a prefix from guild_prefixes");
+        PREFIXES
+            .entry(GuildId(guild_id as u64))
+            .or_default()
+            .push(prefix);
+    }
+    for mut prefixes in PREFIXES.iter_mut() {
+        sort_prefixes(&mut prefixes);
+    }
+
+    let rows = query("SELECT guild_id, group_prefix FROM group_prefixes")
+        .fetch_all(db)
+        .await
+        .expect("Couldn't load the group prefixes");
+
+    for row in rows.iter() {
+        let guild_id: i64 = row
+            .try_get(0)
+            .expect("Couldn't get a guild_id from group_prefixes");
+        GROUP_PREFIXES.insert(
+            GuildId(guild_id as u64),
+            row.try_get(1)
+                .expect("Couldn't get a group_prefix from group_prefixes"),
+        );
+    }
+}
+
+pub fn forget_prefix(guild_id: GuildId) {
+    PREFIXES.remove(&guild_id);
+    GROUP_PREFIXES.remove(&guild_id);
+}
+
+/// The prefix for the commands that aren't in `General Stuff`, empty if there's none
+pub fn group_prefix(guild_id: Option<GuildId>) -> String {
+    match guild_id.and_then(|guild_id| GROUP_PREFIXES.get(&guild_id)) {
+        Some(group_prefix) => group_prefix.clone(),
+        None => BotConfig::get()
+            .map(|config| config.group_prefix().clone())
+            .unwrap_or_default(),
+    }
+}
+
+fn sort_prefixes(prefixes: &mut [String]) {
+    prefixes.sort_by_key(|prefix| Reverse(prefix.chars().count()));
+}
+
 #[command("prefix")]
 #[aliases(
+    "prefixes",
     "setprefix",
     "set_prefix",
     "set-prefix",
@@ -20,66 +93,331 @@ use crate::{
     "change_prefix",
     "change-prefix"
 )]
+#[sub_commands(
+    cmd_prefix_add,
+    cmd_prefix_remove,
+    cmd_prefix_list,
+    cmd_prefix_reset,
+    cmd_prefix_group
+)]
 #[required_permissions("MANAGE_GUILD")]
 #[only_in("guilds")]
 #[bucket = "expensive"]
-#[description = "Change the prefix I'll use in this server\n(It can't end with a space though)"]
-#[usage = "[your prefix]"]
+#[description = "Change the prefix I'll use in this server, this replaces all the others\n(It can't end with a space though)\nYou can also add, remove, list or reset the prefixes if you want more than one, or change the prefix for the other groups"]
+#[usage = "[your prefix, or add, remove, list, reset or group]"]
 #[example = "."]
 async fn cmd_prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (embed, is_error) = set_prefix(ctx, msg.guild_id, args.rest()).await;
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+pub async fn set_prefix(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    prefix: &str,
+) -> (CreateEmbed, bool) {
     let mut embed = CreateEmbed::default();
     let mut is_error = true;
+    let prefix = prefix.trim();
+
+    if let Some((guild_id, db)) = guild_and_db(ctx, guild_id, &mut embed, "prefix", IN_DMS).await {
+        if prefix.chars().count() > 10 {
+            embed
+                .title("Your prefix can't be longer than 10 characters")
+                .description("Why would you want it that long anyway..");
+        } else if let Err(err) = replace_prefixes(&db, guild_id, prefix).await {
+            log(ctx, format!("Couldn't insert to guild_prefixes: {}", err)).await;
+            embed
+                .title("Ugh, I couldn't write that down..")
+                .description("I just let my developer know, until then you could just try again");
+        } else {
+            PREFIXES.insert(guild_id, vec![prefix.to_string()]);
+            is_error = false;
+            embed.description(if !prefix.is_empty() {
+                format!("Voila! My prefix here is now `{}`", prefix)
+            } else {
+                "Yay! I don't even need a prefix here anymore".to_string()
+            });
+        }
+    }
 
-    let data = ctx.data.read().await;
-    let db = data.get::<SqlitePoolKey>();
+    (embed, is_error)
+}
+
+async fn replace_prefixes(db: &AnyPool, guild_id: GuildId, prefix: &str) -> sqlx::Result<()> {
+    let mut transaction = db.begin().await?;
+    query("DELETE FROM guild_prefixes WHERE guild_id = $1")
+        .bind(guild_id.0 as i64)
+        .execute(&mut transaction)
+        .await?;
+    query(
+        "INSERT INTO guild_prefixes (guild_id, prefix)
+        VALUES($1, $2);",
+    )
+    .bind(guild_id.0 as i64)
+    .bind(prefix)
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await
+}
+
+#[command("add")]
+#[aliases("new")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Add a prefix I'll use in this server, without removing the others\n(It can't end with a space though)"]
+#[usage = "[your prefix]"]
+#[example = "search!"]
+async fn cmd_prefix_add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
     let prefix = args.rest().trim();
-    let guild_id = msg.guild_id;
 
-    if let None = guild_id {
-        log(ctx, "msg.guild_id is None for the prefix command").await;
-        embed
-            .title("Something weird happened and I let you use this command in DMs")
-            .description("We have to be in a guild to set the prefix for a guild, no?");
-    };
-    if let None = db {
-        log(
-            ctx,
-            format!("Couldn't get SqlitePool for the prefix command"),
-        )
-        .await;
-        embed
-            .title("Now this is super weird and scary")
-            .description("I lost my whole book where I write things down, sorry..");
-    };
+    if let Some((guild_id, db)) =
+        guild_and_db(ctx, msg.guild_id, &mut embed, "prefix", IN_DMS).await
+    {
+        let max_prefixes = BotConfig::get().map_or(5, |config| config.max_prefixes());
+        let prefix_count = PREFIXES.get(&guild_id).map_or(0, |prefixes| prefixes.len());
 
-    if let (Some(guild_id), Some(db)) = (guild_id, db) {
-        if prefix.chars().count() > 10 {
+        if prefix.is_empty() {
+            embed
+                .title("I need a prefix to add")
+                .description("Type just `prefix` if you don't want me to need one here");
+        } else if prefix.chars().count() > 10 {
             embed
                 .title("Your prefix can't be longer than 10 characters")
                 .description("Why would you want it that long anyway..");
+        } else if PREFIXES
+            .get(&guild_id)
+            .is_some_and(|prefixes| prefixes.iter().any(|p| p == prefix))
+        {
+            embed.title(format!("`{}` is already one of my prefixes here", prefix));
+        } else if prefix_count >= max_prefixes {
+            embed
+                .title(format!(
+                    "You can't have more than {} prefixes",
+                    max_prefixes
+                ))
+                .description("Remove some with `prefix remove` first");
+        } else if let Err(err) = query(
+            "INSERT INTO guild_prefixes (guild_id, prefix)
+            VALUES($1, $2)
+            ON CONFLICT DO NOTHING;",
+        )
+        .bind(guild_id.0 as i64)
+        .bind(prefix)
+        .execute(&db)
+        .await
+        {
+            log(ctx, format!("Couldn't insert to guild_prefixes: {}", err)).await;
+            embed
+                .title("Ugh, I couldn't write that down..")
+                .description("I just let my developer know, until then you could just try again");
         } else {
-            if let Err(err) = query(
-                "INSERT OR REPLACE INTO prefixes (guild_id, prefix)
-                VALUES(?, ?);",
-            )
+            let mut prefixes = PREFIXES.entry(guild_id).or_default();
+            prefixes.push(prefix.to_string());
+            sort_prefixes(&mut prefixes);
+            is_error = false;
+            embed.description(format!(
+                "Voila! `{}` works as a prefix here now too",
+                prefix
+            ));
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[command("remove")]
+#[aliases("delete", "rm", "del")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Remove one of the prefixes I use in this server"]
+#[usage = "[the prefix]"]
+#[example = "search!"]
+async fn cmd_prefix_remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+    let prefix = args.rest().trim();
+
+    if let Some((guild_id, db)) =
+        guild_and_db(ctx, msg.guild_id, &mut embed, "prefix", IN_DMS).await
+    {
+        match query("DELETE FROM guild_prefixes WHERE guild_id = $1 AND prefix = $2")
             .bind(guild_id.0 as i64)
             .bind(prefix)
-            .execute(db)
+            .execute(&db)
+            .await
+        {
+            Err(err) => {
+                log(ctx, format!("Couldn't delete from guild_prefixes: {}", err)).await;
+                embed.title("Ugh, I couldn't erase that..").description(
+                    "I just let my developer know, until then you could just try again",
+                );
+            }
+            Ok(done) if done.rows_affected() == 0 => {
+                embed
+                    .title(format!("`{}` isn't one of my prefixes here", prefix))
+                    .description("Type `prefix list` to see them");
+            }
+            Ok(_) => {
+                if let Some(mut prefixes) = PREFIXES.get_mut(&guild_id) {
+                    prefixes.retain(|p| p != prefix);
+                }
+                is_error = false;
+                embed.description(format!("Poof! `{}` is gone", prefix));
+            }
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[command("list")]
+#[aliases("all", "ls")]
+#[only_in("guilds")]
+#[bucket = "general"]
+#[description = "See the prefixes I use in this server"]
+async fn cmd_prefix_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+
+    let prefixes = msg
+        .guild_id
+        .and_then(|guild_id| PREFIXES.get(&guild_id).map(|prefixes| prefixes.clone()))
+        .unwrap_or_default();
+
+    if prefixes.is_empty() {
+        embed
+            .title("I don't have a prefix here")
+            .description("Just mention me or add one with `prefix add`");
+    } else {
+        embed.title("My prefixes here").description(
+            prefixes
+                .iter()
+                .map(|prefix| {
+                    if prefix.is_empty() {
+                        "*No prefix at all*".to_string()
+                    } else {
+                        format!("`{}`", prefix)
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+        );
+    }
+
+    send_embed(ctx, msg, false, embed).await;
+    Ok(())
+}
+
+#[command("reset")]
+#[aliases("clear")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Remove all the prefixes I use in this server, so you have to mention me"]
+async fn cmd_prefix_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+
+    if let Some((guild_id, db)) =
+        guild_and_db(ctx, msg.guild_id, &mut embed, "prefix", IN_DMS).await
+    {
+        if let Err(err) = query("DELETE FROM guild_prefixes WHERE guild_id = $1")
+            .bind(guild_id.0 as i64)
+            .execute(&db)
             .await
-            {
-                log(ctx, format!("Couldn't insert to prefixes: {}", err)).await;
+        {
+            log(ctx, format!("Couldn't delete from guild_prefixes: {}", err)).await;
+            embed
+                .title("Ugh, I couldn't erase that..")
+                .description("I just let my developer know, until then you could just try again");
+        } else {
+            PREFIXES.remove(&guild_id);
+            is_error = false;
+            embed.description("Done! You'll have to mention me here now");
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[command("group")]
+#[aliases("groups", "group_prefix", "group-prefix")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Change the prefix for the commands that aren't in `General Stuff`\n`off` means they only work with my normal prefixes and `reset` goes back to the default"]
+#[usage = "[the group prefix, off or reset]"]
+#[example = "search."]
+async fn cmd_prefix_group(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+    let prefix = args.rest().trim();
+
+    if prefix.is_empty() {
+        is_error = false;
+        let current = group_prefix(msg.guild_id);
+        if current.is_empty() {
+            embed.title("The groups don't have a prefix here");
+        } else {
+            embed.title(format!("The groups' prefix here is `{}`", current));
+        }
+        embed.description("Type `help prefix group` if you want to change it");
+    } else if prefix.chars().count() > 10 {
+        embed
+            .title("The group prefix can't be longer than 10 characters")
+            .description("Why would you want it that long anyway..");
+    } else if let Some((guild_id, db)) =
+        guild_and_db(ctx, msg.guild_id, &mut embed, "prefix", IN_DMS).await
+    {
+        let result = match prefix.to_lowercase().as_str() {
+            "reset" => query("DELETE FROM group_prefixes WHERE guild_id = $1")
+                .bind(guild_id.0 as i64)
+                .execute(&db)
+                .await
+                .map(|_| None),
+            "off" => set_group_prefix(&db, guild_id, "").await.map(|_| Some("")),
+            _ => set_group_prefix(&db, guild_id, prefix)
+                .await
+                .map(|_| Some(prefix)),
+        };
+
+        match result {
+            Err(err) => {
+                log(ctx, format!("Couldn't write to group_prefixes: {}", err)).await;
                 embed
                     .title("Ugh, I couldn't write that down..")
                     .description(
                         "I just let my developer know, until then you could just try again",
                     );
-            } else {
+            }
+            Ok(None) => {
+                GROUP_PREFIXES.remove(&guild_id);
+                is_error = false;
+                embed.description(format!(
+                    "Done! The groups are back to the default prefix `{}`",
+                    group_prefix(Some(guild_id))
+                ));
+            }
+            Ok(Some("")) => {
+                GROUP_PREFIXES.insert(guild_id, String::new());
+                is_error = false;
+                embed.description("Done! The groups only work with my normal prefixes here now");
+            }
+            Ok(Some(prefix)) => {
+                GROUP_PREFIXES.insert(guild_id, prefix.to_string());
                 is_error = false;
-                embed.description(if prefix != "" {
-                    format!("Voila! My prefix here is now `{}`", prefix)
-                } else {
-                    "Yay! I don't even need a prefix here anymore".to_string()
-                });
+                embed.description(format!(
+                    "Voila! The groups' prefix here is now `{}`",
+                    prefix
+                ));
             }
         }
     }
@@ -88,60 +426,200 @@ async fn cmd_prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     Ok(())
 }
 
-pub async fn prefix_check(ctx: &Context, msg: &Message) -> Option<String> {
-    let guild_id = msg.guild_id?;
+async fn set_group_prefix(db: &AnyPool, guild_id: GuildId, group_prefix: &str) -> sqlx::Result<()> {
+    query(
+        "INSERT INTO group_prefixes (guild_id, group_prefix)
+        VALUES($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET group_prefix = excluded.group_prefix;",
+    )
+    .bind(guild_id.0 as i64)
+    .bind(group_prefix)
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+pub async fn prefix_check(_ctx: &Context, msg: &Message) -> Option<String> {
+    command_prefix(msg.guild_id?, msg.channel_id, &msg.content)
+}
+
+/// The prefix to use for the message, it only reads the prefixes cached by [`load_prefixes`]
+pub fn command_prefix(guild_id: GuildId, channel_id: ChannelId, content: &str) -> Option<String> {
     let cmd_info = CmdInfo::get()?;
-    let content = msg.content.as_str();
 
-    let mut is_cmd = false;
-    for cmd in cmd_info.cmds().iter() {
-        if content.contains(cmd) {
-            if content.starts_with(".") && cmd_info.custom_cmds().contains(cmd) {
-                return Some(".".to_string());
+    let group_prefix = group_prefix(Some(guild_id));
+    if !group_prefix.is_empty() {
+        if let Some(rest) = strip_prefix_ignore_case(content, &group_prefix) {
+            let name = cmd_name(rest);
+            if cmd_info.is_custom_cmd(name) || has_guild_site(guild_id, name) {
+                return Some(group_prefix);
             }
-            is_cmd = true;
-            break;
         }
     }
-    if !is_cmd {
-        return None;
+
+    let is_cmd = |prefix: &str| {
+        strip_prefix_ignore_case(content, prefix).is_some_and(|rest| {
+            let name = cmd_name(rest);
+            cmd_info.is_cmd(name) || has_guild_site(guild_id, name)
+        })
+    };
+    if let Some(prefix) = channel_prefix(channel_id) {
+        return is_cmd(&prefix).then_some(prefix);
     }
 
-    let data = ctx.data.read().await;
-    let db = match data.get::<SqlitePoolKey>() {
-        Some(db) => db,
-        None => {
-            log(ctx, "Couldn't get the database for the prefix check").await;
+    PREFIXES
+        .get(&guild_id)?
+        .iter()
+        .find(|prefix| is_cmd(prefix))
+        .cloned()
+}
+
+fn cmd_name(rest: &str) -> &str {
+    rest.split(char::is_whitespace).next().unwrap_or_default()
+}
+
+fn strip_prefix_ignore_case<'a>(content: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut chars = content.char_indices();
+    for expected in prefix.chars() {
+        let (_, c) = chars.next()?;
+        if !c.to_lowercase().eq(expected.to_lowercase()) {
             return None;
         }
-    };
+    }
+    Some(chars.as_str())
+}
 
-    match query("SELECT prefix FROM prefixes WHERE guild_id = ?")
-        .bind(guild_id.0 as i64)
-        .fetch_optional(db)
-        .await
-    {
-        Err(err) => {
-            log(
-                ctx,
-                format!(
-                    "Couldn't fetch prefix from the database for the prefix check: {:?}",
-                    err
-                ),
-            )
-            .await;
-            None
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use serenity::framework::standard::macros::group;
+
+    use super::*;
+    use crate::GENERAL_GROUP;
+
+    #[command("google")]
+    #[aliases("s", "search")]
+    async fn test_google(_ctx: &Context, _msg: &Message) -> CommandResult {
+        Ok(())
+    }
+
+    #[command("image")]
+    #[aliases("i")]
+    async fn test_image(_ctx: &Context, _msg: &Message) -> CommandResult {
+        Ok(())
+    }
+
+    #[command("dictionary")]
+    #[aliases("d")]
+    async fn test_dictionary(_ctx: &Context, _msg: &Message) -> CommandResult {
+        Ok(())
+    }
+
+    #[group("Search Things")]
+    #[commands(test_google, test_image, test_dictionary)]
+    struct Search;
+
+    static SET_CMD_INFO: Once = Once::new();
+
+    /// A guild with these prefixes and group prefix, each test uses its own ID
+    fn guild(guild_id: u64, prefixes: &[&str], group_prefix: &str) -> GuildId {
+        SET_CMD_INFO.call_once(|| CmdInfo::set(&[&GENERAL_GROUP, &SEARCH_GROUP]));
+
+        let guild_id = GuildId(guild_id);
+        let mut prefixes: Vec<String> = prefixes.iter().map(|prefix| prefix.to_string()).collect();
+        sort_prefixes(&mut prefixes);
+        PREFIXES.insert(guild_id, prefixes);
+        GROUP_PREFIXES.insert(guild_id, group_prefix.to_string());
+        guild_id
+    }
+
+    fn check(guild_id: GuildId, cases: &[(&str, Option<&str>)]) {
+        for (content, prefix) in cases.iter() {
+            assert_eq!(
+                command_prefix(guild_id, ChannelId(1), content).as_deref(),
+                *prefix,
+                "command_prefix({:?})",
+                content
+            );
         }
-        Ok(row) => match row?.try_get(0) {
-            Ok(prefix) => prefix,
-            Err(err) => {
-                log(
-                    ctx,
-                    format!("Couldn't get the prefix column for the guild: {:?}", err),
-                )
-                .await;
-                None
-            }
-        },
+    }
+
+    #[test]
+    fn ignores_messages_that_arent_commands() {
+        let guild_id = guild(1, &["?"], ".");
+        check(
+            guild_id,
+            &[
+                ("i think so", None),
+                ("d", None),
+                ("hello .google", None),
+                ("hello ?google", None),
+                ("?googlex", None),
+                ("?googlex cats", None),
+                (".googlex", None),
+                ("?", None),
+                ("", None),
+                // Only the search commands have the group prefix
+                (".info", None),
+                (".prefix ?", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_commands_right_after_the_prefix() {
+        let guild_id = guild(2, &["?", "search!"], ".");
+        check(
+            guild_id,
+            &[
+                ("?google cats", Some("?")),
+                ("?GOOGLE cats", Some("?")),
+                ("?d", Some("?")),
+                ("?d\ncats", Some("?")),
+                ("?info", Some("?")),
+                ("?help", Some("?")),
+                ("search!i koalas", Some("search!")),
+                ("SEARCH!i koalas", Some("search!")),
+                (".google cats", Some(".")),
+                (".s cats", Some(".")),
+            ],
+        );
+    }
+
+    #[test]
+    fn ignores_the_group_prefix_when_its_off() {
+        let guild_id = guild(3, &["?"], "");
+        check(
+            guild_id,
+            &[
+                (".google cats", None),
+                ("google cats", None),
+                ("?google cats", Some("?")),
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_multi_byte_prefixes_ignoring_case() {
+        let guild_id = guild(4, &["Ä!", "поиск "], "ß.");
+        check(
+            guild_id,
+            &[
+                ("ä!google cats", Some("Ä!")),
+                ("Ä!google cats", Some("Ä!")),
+                ("ä!googlex", None),
+                ("ПОИСК google cats", Some("поиск ")),
+                ("Поиск d слово", Some("поиск ")),
+                ("поискgoogle", None),
+                ("ß.google cats", Some("ß.")),
+                ("ß.info", None),
+            ],
+        );
+
+        assert_eq!(strip_prefix_ignore_case("ÄBC", "äb"), Some("C"));
+        assert_eq!(strip_prefix_ignore_case("ÉCOLE", "école"), Some(""));
+        assert_eq!(strip_prefix_ignore_case("a", "ab"), None);
+        assert_eq!(strip_prefix_ignore_case("日本語", "日本"), Some("語"));
     }
 }
@@ -0,0 +1,192 @@
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use serenity::{
+    builder::CreateEmbed,
+    client::{bridge::gateway::ShardId, Context},
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, id::GuildId},
+};
+use sqlx::{query, AnyPool, Row};
+
+use crate::{
+    cmd_bucket, cmd_channel, cmd_prefix, cmd_site,
+    globals::{BotConfig, DatabaseKey, ShardManagerKey},
+    log, send_embed,
+};
+
+const GUILD_TABLES: [&str; 5] = [
+    "guild_prefixes",
+    "group_prefixes",
+    "guild_sites",
+    "channel_settings",
+    "bucket_overrides",
+];
+
+static CACHE_READY: AtomicBool = AtomicBool::new(false);
+
+pub fn set_cache_ready() {
+    CACHE_READY.store(true, Ordering::Relaxed);
+}
+
+/// Deletes everything about the guild from the database and the caches
+pub async fn forget_guild(db: &AnyPool, guild_id: GuildId) -> sqlx::Result<()> {
+    let mut transaction = db.begin().await?;
+    for table in GUILD_TABLES.iter() {
+        query(&format!("DELETE FROM {} WHERE guild_id = $1", table))
+            .bind(guild_id.0 as i64)
+            .execute(&mut transaction)
+            .await?;
+    }
+    transaction.commit().await?;
+
+    cmd_prefix::forget_prefix(guild_id);
+    cmd_site::forget_guild_sites(guild_id);
+    cmd_channel::forget_channel_settings(guild_id);
+    cmd_bucket::forget_bucket_overrides(guild_id);
+    Ok(())
+}
+
+/// Forgets the guild after `forget_guild_hours`, unless I'm added back to it before that
+pub async fn forget_guild_later(ctx: Context, guild_id: GuildId) {
+    let hours = match BotConfig::get() {
+        Some(config) => config.forget_guild_hours(),
+        None => {
+            log(&ctx, "Couldn't get BotConfig to get forget_guild_hours").await;
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(hours * 60 * 60)).await;
+        if ctx.cache.guild_field(guild_id, |_| ()).await.is_some() {
+            return;
+        }
+
+        let db = match ctx.data.read().await.get::<DatabaseKey>().cloned() {
+            Some(db) => db,
+            None => {
+                log(&ctx, "Couldn't get the database to forget a guild").await;
+                return;
+            }
+        };
+        if let Err(err) = forget_guild(&db, guild_id).await {
+            log(
+                &ctx,
+                format!("Couldn't forget the guild {}: {}", guild_id, err),
+            )
+            .await;
+        }
+    });
+}
+
+#[command("purge")]
+#[owners_only]
+#[help_available(false)]
+#[description = "Delete everything about the guilds I'm not in anymore, only the ones on the shards this process runs\nType `purge confirm` to really do it"]
+#[usage = "[confirm]"]
+async fn cmd_purge(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+    let db = ctx.data.read().await.get::<DatabaseKey>().cloned();
+    let shards = my_shards(ctx).await;
+
+    if !CACHE_READY.load(Ordering::Relaxed) {
+        embed
+            .title("I don't know all my guilds yet")
+            .description("Wait a bit until I'm fully ready so I don't delete the wrong ones");
+    } else if shards.is_none() {
+        log(ctx, "Couldn't get the shard manager for the purge command").await;
+        embed
+            .title("I lost track of my shards")
+            .description("So I can't tell which guilds are mine to clean up, sorry..");
+    } else if let (Some(db), Some(shards)) = (db, shards) {
+        match orphaned_guilds(ctx, &db, &shards).await {
+            Err(err) => {
+                log(ctx, format!("Couldn't look for orphaned guilds: {}", err)).await;
+                embed
+                    .title("Ugh, I couldn't read my notes..")
+                    .description(err);
+            }
+            Ok(orphans) if orphans.is_empty() => {
+                is_error = false;
+                embed.description("Everything's clean already ✨");
+            }
+            Ok(orphans) if !args.rest().trim().eq_ignore_ascii_case("confirm") => {
+                is_error = false;
+                embed
+                    .title(format!(
+                        "I'm not in {} of the guilds I have notes for",
+                        orphans.len()
+                    ))
+                    .description("Type `purge confirm` to delete them");
+            }
+            Ok(orphans) => {
+                let mut purged = 0;
+                for guild_id in orphans.iter() {
+                    match forget_guild(&db, *guild_id).await {
+                        Ok(_) => purged += 1,
+                        Err(err) => {
+                            log(
+                                ctx,
+                                format!("Couldn't forget the guild {}: {}", guild_id, err),
+                            )
+                            .await
+                        }
+                    }
+                }
+                is_error = purged != orphans.len();
+                embed.description(format!(
+                    "Poof! Forgot {} of {} guilds",
+                    purged,
+                    orphans.len()
+                ));
+            }
+        }
+    } else {
+        log(ctx, "Couldn't get the database for the purge command").await;
+        embed
+            .title("Now this is super weird and scary")
+            .description("I lost my whole book where I write things down, sorry..");
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+/// The shards this process runs, the cache only has the guilds on these
+async fn my_shards(ctx: &Context) -> Option<HashSet<u64>> {
+    let manager = ctx.data.read().await.get::<ShardManagerKey>().cloned()?;
+    let shards = manager.lock().await.shards_instantiated().await;
+    Some(shards.into_iter().map(|ShardId(id)| id).collect())
+}
+
+/// The guilds I have notes for but am not in anymore, leaving out the ones on shards another
+/// process runs since those aren't in my cache
+async fn orphaned_guilds(
+    ctx: &Context,
+    db: &AnyPool,
+    shards: &HashSet<u64>,
+) -> sqlx::Result<Vec<GuildId>> {
+    let guilds: HashSet<GuildId> = ctx.cache.guilds().await.into_iter().collect();
+    let shard_count = ctx.cache.shard_count().await.max(1);
+
+    let mut orphans = HashSet::new();
+    for table in GUILD_TABLES.iter() {
+        let rows = query(&format!("SELECT DISTINCT guild_id FROM {}", table))
+            .fetch_all(db)
+            .await?;
+        for row in rows.iter() {
+            let guild_id = GuildId(row.try_get::<i64, _>(0)? as u64);
+            let shard = (guild_id.0 >> 22) % shard_count;
+            if shards.contains(&shard) && !guilds.contains(&guild_id) {
+                orphans.insert(guild_id);
+            }
+        }
+    }
+
+    Ok(orphans.into_iter().collect())
+}
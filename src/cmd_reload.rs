@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    framework::standard::{macros::command, CommandResult},
+    model::channel::Message,
+};
+
+use crate::{
+    definitions::{truncate, MAX_DESCRIPTION_LEN},
+    globals::{report, BotConfig},
+    logging::{log_at, Level},
+    send_embed,
+};
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Reloads the config and logs how it went, returning the embed to tell it with
+pub fn reload(ctx: &Context) -> (CreateEmbed, bool) {
+    let mut embed = CreateEmbed::default();
+    match BotConfig::reload() {
+        Ok(restart_required) if restart_required.is_empty() => {
+            log_at(ctx, Level::Info, "Reloaded the config");
+            embed.description("Read the config again, everything's up to date ✨");
+            (embed, false)
+        }
+        Ok(restart_required) => {
+            let fields = restart_required
+                .iter()
+                .map(|field| format!("`{}`", field))
+                .collect::<Vec<String>>()
+                .join(", ");
+            log_at(
+                ctx,
+                Level::Warn,
+                format!(
+                    "Reloaded the config but these need a restart to change: {}",
+                    fields
+                ),
+            );
+            embed
+                .title("Read the config again, but restart me for these please")
+                .description(format!(
+                    "{}\nI can only read them when I start so I'm still using the old ones",
+                    fields
+                ));
+            (embed, true)
+        }
+        Err(problems) => {
+            let report = report(&problems);
+            log_at(
+                ctx,
+                Level::Warn,
+                format!("Couldn't reload the config, kept the old one\n{}", report),
+            );
+            embed
+                .title("Your config has some problems so I kept the old one")
+                .description(truncate(&report, MAX_DESCRIPTION_LEN));
+            (embed, true)
+        }
+    }
+}
+
+/// Reloads the config every time I get SIGHUP, only the first call does anything
+#[cfg(unix)]
+pub fn reload_on_sighup(ctx: &Context) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    if LISTENING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                log_at(
+                    &ctx,
+                    Level::Error,
+                    format!("Couldn't listen for SIGHUP to reload the config: {}", err),
+                );
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            reload(&ctx);
+        }
+    });
+}
+
+/// There's no SIGHUP here, the `reload` command still works though
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_ctx: &Context) {
+    LISTENING.store(true, Ordering::Release);
+}
+
+#[command("reload")]
+#[owners_only]
+#[help_available(false)]
+#[description = "Read the config file again without restarting"]
+async fn cmd_reload(ctx: &Context, msg: &Message) -> CommandResult {
+    let (embed, is_error) = reload(ctx);
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
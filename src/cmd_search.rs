@@ -1,156 +1,875 @@
-use once_cell::sync::OnceCell;
+use std::{collections::HashMap, sync::Mutex};
+
+use once_cell::sync::{Lazy, OnceCell};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Deserialize;
 use serenity::{
     builder::{CreateEmbed, CreateEmbedAuthor},
     client::Context,
-    framework::standard::{macros::command, Args, CommandResult},
+    framework::standard::{
+        Args, Check, Command, CommandGroup, CommandOptions, CommandResult, GroupOptions,
+    },
+    futures::future::BoxFuture,
     model::channel::Message,
 };
-use url::Url;
+use url::{form_urlencoded, Url};
 
-use crate::{log, send_embed};
+use crate::{
+    cmd_block::NOT_BLOCKED_CHECK,
+    cmd_bucket::{Bucket, RATE_LIMIT_CHECK},
+    cmd_channel::ENABLED_HERE_CHECK,
+    definitions,
+    globals::{check_url, BotConfig, ConfigProblem},
+    log, send_embed, send_pages,
+};
 
-static GOOGLE: OnceCell<(CreateEmbedAuthor, Url, &'static str)> = OnceCell::new();
-static IMAGE: OnceCell<(CreateEmbedAuthor, Url, &'static str)> = OnceCell::new();
-static DICTIONARY: OnceCell<(CreateEmbedAuthor, Url, &'static str)> = OnceCell::new();
-static URBAN: OnceCell<(CreateEmbedAuthor, Url, &'static str)> = OnceCell::new();
+pub const DEFAULT_SITES: &str = "# Google, Google Images, Wiktionary and Urban Dictionary are already there but you can add your own sites here, each one becomes a command
+# name: The name of the command, if it's the same as one that's already there it replaces it
+# aliases: Other names you can use the command with
+# title: The name that's shown at the top of the result
+# url: The link the search term is added to
+# template: What's added to the link, {} is replaced with the search term
+# mode: \"query\" to put the template after a ?, \"path\" to put it after the /
+# icon: The link of the image that's shown next to the title
+# description, usage, example: What the help command will show for it
+# bucket: The rate limit in [buckets] to use, general if it isn't there
+# Remove the # at the start of the lines to use it
+# [[sites]]
+# name = \"duckduckgo\"
+# aliases = [\"ddg\", \"duck\"]
+# title = \"DuckDuckGo\"
+# url = \"https://duckduckgo.com/\"
+# template = \"q={}\"
+# mode = \"query\"
+# icon = \"https://duckduckgo.com/assets/icons/meta/DDG-icon_256x256.png\"
+# description = \"Let me search DuckDuckGo for you\"
+# usage = \"[what you want me to search for]\"
+# example = \"how do ducks sleep\"";
 
-pub fn set_sites() {
-    let mut author_google = CreateEmbedAuthor::default();
-    author_google
-        .name("Google")
-        .url("https://www.google.com/")
-        .icon_url("https://upload.wikimedia.org/wikipedia/commons/thumb/5/53/Google_%22G%22_Logo.svg/500px-Google_%22G%22_Logo.svg.png");
-    let mut author_image = CreateEmbedAuthor::default();
-    author_image
-        .name("Google Images")
-        .url("https://images.google.com/")
-        .icon_url("https://upload.wikimedia.org/wikipedia/commons/thumb/5/53/Google_%22G%22_Logo.svg/500px-Google_%22G%22_Logo.svg.png");
-    let mut author_dictionary = CreateEmbedAuthor::default();
-    author_dictionary
-        .name("Wiktionary")
-        .url("https://en.wiktionary.org/")
-        .icon_url("https://upload.wikimedia.org/wikipedia/commons/0/07/Wiktsister_en.png");
-    let mut author_urban = CreateEmbedAuthor::default();
-    author_urban
-        .name("Urban Dictionary")
-        .url("https://www.urbandictionary.com/")
-        .icon_url("https://static.wikia.nocookie.net/logopedia/images/0/0b/UDFavicon.png");
-
-    GOOGLE
-        .set((
-            author_google,
-            Url::parse("https://www.google.com/search").unwrap(),
-            "q=",
-        ))
-        .unwrap();
-    IMAGE
-        .set((
-            author_image,
-            Url::parse("https://www.google.com/search").unwrap(),
-            "tbm=isch&q=",
-        ))
-        .unwrap();
-    DICTIONARY
-        .set((
-            author_dictionary,
-            Url::parse("https://en.wiktionary.org").unwrap(),
-            "wiki/",
-        ))
-        .unwrap();
-    URBAN
-        .set((
-            author_urban,
-            Url::parse("https://www.urbandictionary.com/define.php").unwrap(),
-            "term=",
-        ))
-        .unwrap();
-}
-
-async fn get_search_embed(
-    ctx: &Context,
-    args: Args,
-    site: Option<&(CreateEmbedAuthor, Url, &'static str)>,
-    is_dictionary: bool,
-) -> (CreateEmbed, bool) {
-    let mut embed = CreateEmbed::default();
-    let term = args.rest().trim();
+/// What a search command is called and how it shows up in the results and the help command
+pub struct SiteInfo {
+    /// The command's name first, then its aliases
+    pub names: Vec<String>,
+    pub title: String,
+    pub home: String,
+    pub icon: String,
+    pub description: String,
+    pub usage: Option<String>,
+    pub example: Option<String>,
+}
 
-    if term == "" {
-        embed.title("I need something to search for though..");
-        return (embed, true);
+/// A site that can be searched on, each one becomes a command in `Search Things`
+///
+/// Add your own with [`register_provider`] before calling [`set_sites`]
+#[serenity::async_trait]
+pub trait SearchProvider: Send + Sync {
+    fn info(&self) -> SiteInfo;
+
+    /// The link that opens the results for the term, `None` if it can't make one
+    fn url(&self, term: &str) -> Option<Url>;
+
+    /// The name of the bucket in the config that limits how often it can be used
+    fn bucket(&self) -> String {
+        "general".to_string()
+    }
+
+    /// Adds things like the definitions of the term to the embed, it already has the link
+    async fn preview(&self, _ctx: &Context, _term: &str, _embed: &mut CreateEmbed) {}
+
+    /// Embeds to show instead of the link, which can be paged through with ⬅️ and ➡️
+    async fn pages(&self, _ctx: &Context, _term: &str, _link: &Url) -> Vec<CreateEmbed> {
+        Vec::new()
+    }
+}
+
+pub struct Google;
+impl SearchProvider for Google {
+    fn info(&self) -> SiteInfo {
+        SiteInfo {
+            names: names(&["google", "s", "search"]),
+            title: "Google".to_string(),
+            home: "https://www.google.com/".to_string(),
+            icon: GOOGLE_ICON.to_string(),
+            description: "Let me help you google something".to_string(),
+            usage: Some("[what you want me to google]".to_string()),
+            example: Some("what's it like to feel emotions".to_string()),
+        }
+    }
+
+    fn url(&self, term: &str) -> Option<Url> {
+        query_url("https://www.google.com/search", "q={}", term)
+    }
+}
+
+pub struct GoogleImages;
+impl SearchProvider for GoogleImages {
+    fn info(&self) -> SiteInfo {
+        SiteInfo {
+            names: names(&[
+                "image",
+                "i",
+                "images",
+                "imagesearch",
+                "image-search",
+                "image_search",
+            ]),
+            title: "Google Images".to_string(),
+            home: "https://images.google.com/".to_string(),
+            icon: GOOGLE_ICON.to_string(),
+            description: "Let me search Google images for you".to_string(),
+            usage: Some("[what you want me to search google images for]".to_string()),
+            example: Some("cute koalas".to_string()),
+        }
+    }
+
+    fn url(&self, term: &str) -> Option<Url> {
+        query_url("https://www.google.com/search", "tbm=isch&q={}", term)
+    }
+}
+
+pub struct Wiktionary;
+#[serenity::async_trait]
+impl SearchProvider for Wiktionary {
+    fn info(&self) -> SiteInfo {
+        SiteInfo {
+            names: names(&["dictionary", "d", "wiktionary", "definition", "define", "meaning"]),
+            title: "Wiktionary".to_string(),
+            home: "https://en.wiktionary.org/".to_string(),
+            icon: "https://upload.wikimedia.org/wikipedia/commons/0/07/Wiktsister_en.png".to_string(),
+            description: "Don't know a word? Let me help you look it up on Wiktionary for you\n(Wiktionary is like the Wikipedia of words and supports practically any language! It also has all sorts of info like pronunciation, etymology, examples etc. Seriously it's great)".to_string(),
+            usage: Some("[what you don't know the definition of]".to_string()),
+            example: Some("wie geht es dir".to_string()),
+        }
+    }
+
+    fn url(&self, term: &str) -> Option<Url> {
+        path_url("https://en.wiktionary.org", "wiki/{}", term)
+    }
+
+    async fn preview(&self, ctx: &Context, term: &str, embed: &mut CreateEmbed) {
+        definitions::wiktionary(ctx, term, embed).await;
+    }
+}
+
+pub struct UrbanDictionary;
+#[serenity::async_trait]
+impl SearchProvider for UrbanDictionary {
+    fn info(&self) -> SiteInfo {
+        SiteInfo {
+            names: names(&["urban", "u"]),
+            title: "Urban Dictionary".to_string(),
+            home: "https://www.urbandictionary.com/".to_string(),
+            icon: "https://static.wikia.nocookie.net/logopedia/images/0/0b/UDFavicon.png"
+                .to_string(),
+            description:
+                "Don't know the latest internet words and feeling like a boomer? Now I can help"
+                    .to_string(),
+            usage: Some("[the edgy phrase you want to learn]".to_string()),
+            example: Some("third wheel".to_string()),
+        }
+    }
+
+    fn url(&self, term: &str) -> Option<Url> {
+        query_url(
+            "https://www.urbandictionary.com/define.php",
+            "term={}",
+            term,
+        )
     }
 
-    let target = match site {
-        Some((author, url, query)) => {
-            let mut url = url.clone();
-            let rest = &format!("{}{}", query, term);
-            if is_dictionary {
-                url.set_path(rest)
-            } else {
-                url.set_query(Some(rest));
+    async fn pages(&self, ctx: &Context, term: &str, link: &Url) -> Vec<CreateEmbed> {
+        match BotConfig::get() {
+            Some(config) if config.urban_definitions() => {
+                definitions::urban(ctx, term, link.as_str()).await
             }
-            Some((author, url))
+            _ => Vec::new(),
         }
-        None => None,
+    }
+}
+
+const GOOGLE_ICON: &str = "https://upload.wikimedia.org/wikipedia/commons/thumb/5/53/Google_%22G%22_Logo.svg/500px-Google_%22G%22_Logo.svg.png";
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+// Everything that isn't allowed in a path or changes what the path means, including /, ? and #
+// so "and/or" stays one page instead of going into the "and" folder
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Encodes the term so that it can be put in a query, like `q={}`
+pub fn encode_query(term: &str) -> String {
+    form_urlencoded::byte_serialize(term.as_bytes()).collect()
+}
+
+/// Encodes the term so that it stays a single part of a path, like `wiki/{}`
+///
+/// `None` for `.` and `..` since links can't have them as a part of the path, even encoded
+pub fn encode_path(term: &str) -> Option<String> {
+    match term {
+        "." | ".." => None,
+        _ => Some(utf8_percent_encode(term, PATH_SEGMENT).to_string()),
+    }
+}
+
+/// Adds the template to the query of the link with `{}` replaced by the encoded term
+pub fn query_url(base: &str, template: &str, term: &str) -> Option<Url> {
+    let mut url = Url::parse(base).ok()?;
+    let rest = template.replace("{}", &encode_query(term));
+    let query = match url.query() {
+        Some(query) if !query.is_empty() => format!("{}&{}", query, rest),
+        _ => rest,
     };
+    url.set_query(Some(&query));
+    Some(url)
+}
+
+/// Sets the path of the link to the template with `{}` replaced by the encoded term
+pub fn path_url(base: &str, template: &str, term: &str) -> Option<Url> {
+    let mut url = Url::parse(base).ok()?;
+    url.set_path(&template.replace("{}", &encode_path(term)?));
+    Some(url)
+}
+
+/// Replaces `{}` in a whole link with the term, encoded for the query if it comes after a `?` or
+/// `#` and for the path if not
+pub fn template_url(template: &str, term: &str) -> Option<Url> {
+    let before = template.split("{}").next()?;
+    let encoded = if before.contains('?') || before.contains('#') {
+        encode_query(term)
+    } else {
+        encode_path(term)?
+    };
+    Url::parse(&template.replace("{}", &encoded)).ok()
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SiteMode {
+    Query,
+    Path,
+}
+
+#[derive(Clone, Deserialize, PartialEq)]
+pub struct Site {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    title: String,
+    url: String,
+    template: String,
+    mode: SiteMode,
+    icon: String,
+    description: String,
+    usage: Option<String>,
+    example: Option<String>,
+    bucket: Option<String>,
+}
+
+impl Site {
+    pub fn validate(
+        &self,
+        field: &str,
+        buckets: &HashMap<String, Bucket>,
+        problems: &mut Vec<ConfigProblem>,
+    ) {
+        let names = std::iter::once((format!("{}.name", field), &self.name)).chain(
+            self.aliases
+                .iter()
+                .enumerate()
+                .map(|(i, alias)| (format!("{}.aliases[{}]", field, i), alias)),
+        );
+        for (field, name) in names {
+            let trimmed = name.trim();
+            if trimmed.is_empty() {
+                problems.push(ConfigProblem::new(
+                    field,
+                    name,
+                    "It's empty so the site wouldn't have a command",
+                    "Give it a name without spaces, like google",
+                ));
+            } else if trimmed.contains(char::is_whitespace) {
+                problems.push(ConfigProblem::new(
+                    field,
+                    name,
+                    "Commands can't have spaces in them",
+                    "Use - or _ instead, like duck-duck",
+                ));
+            } else if crate::builtin_cmds().any(|cmd| cmd.eq_ignore_ascii_case(trimmed)) {
+                problems.push(ConfigProblem::new(
+                    field,
+                    name,
+                    "I already have a command with this name",
+                    "Give it another name",
+                ));
+            }
+        }
+        if !self.template.contains("{}") {
+            problems.push(ConfigProblem::new(
+                format!("{}.template", field),
+                &self.template,
+                format!(
+                    "The site {} doesn't have {{}} in it, where would the search term go?",
+                    self.name
+                ),
+                "Put {} where the search term goes, like q={}",
+            ));
+        }
+        check_url(&format!("{}.url", field), &self.url, problems);
+        check_url(&format!("{}.icon", field), &self.icon, problems);
+        if let Some(bucket) = &self.bucket {
+            if !buckets.contains_key(bucket) {
+                problems.push(ConfigProblem::new(
+                    format!("{}.bucket", field),
+                    bucket,
+                    "There's no bucket with this name",
+                    "Add it to [buckets] or use general or expensive",
+                ));
+            }
+        }
+    }
+}
+
+impl SearchProvider for Site {
+    fn info(&self) -> SiteInfo {
+        SiteInfo {
+            names: std::iter::once(&self.name)
+                .chain(self.aliases.iter())
+                .cloned()
+                .collect(),
+            title: self.title.clone(),
+            home: Url::parse(&self.url)
+                .and_then(|url| url.join("/"))
+                .map_or_else(|_| self.url.clone(), String::from),
+            icon: self.icon.clone(),
+            description: self.description.clone(),
+            usage: self.usage.clone(),
+            example: self.example.clone(),
+        }
+    }
+
+    fn url(&self, term: &str) -> Option<Url> {
+        match self.mode {
+            SiteMode::Path => path_url(&self.url, &self.template, term),
+            SiteMode::Query => query_url(&self.url, &self.template, term),
+        }
+    }
+
+    fn bucket(&self) -> String {
+        self.bucket.clone().unwrap_or_else(|| "general".to_string())
+    }
+}
+
+struct SearchSite {
+    names: &'static [&'static str],
+    author: CreateEmbedAuthor,
+    provider: &'static dyn SearchProvider,
+}
+
+static PROVIDERS: Lazy<Mutex<Vec<Box<dyn SearchProvider>>>> = Lazy::new(|| {
+    Mutex::new(vec![
+        Box::new(Google),
+        Box::new(GoogleImages),
+        Box::new(Wiktionary),
+        Box::new(UrbanDictionary),
+    ])
+});
+static SITES: OnceCell<Vec<SearchSite>> = OnceCell::new();
+static SEARCH_CHECKS: [&Check; 3] = [&NOT_BLOCKED_CHECK, &ENABLED_HERE_CHECK, &RATE_LIMIT_CHECK];
+static SEARCH_GROUP: OnceCell<CommandGroup> = OnceCell::new();
+
+/// Adds a problem for every name or alias more than one site has, a default site only counts if
+/// no site in the config has its name to replace it
+pub fn validate_site_names(sites: &[Site], problems: &mut Vec<ConfigProblem>) {
+    let mut taken: HashMap<String, String> = HashMap::new();
+    for info in PROVIDERS
+        .lock()
+        .expect("Couldn't lock PROVIDERS to check the site names")
+        .iter()
+        .map(|provider| provider.info())
+    {
+        let is_replaced = info.names.first().is_some_and(|default_name| {
+            sites
+                .iter()
+                .any(|site| site.name.trim().eq_ignore_ascii_case(default_name.trim()))
+        });
+        if !is_replaced {
+            for name in info.names.iter() {
+                taken.insert(name.trim().to_lowercase(), info.title.clone());
+            }
+        }
+    }
+
+    for (i, site) in sites.iter().enumerate() {
+        let names = std::iter::once((format!("sites[{}].name", i), &site.name)).chain(
+            site.aliases
+                .iter()
+                .enumerate()
+                .map(|(j, alias)| (format!("sites[{}].aliases[{}]", i, j), alias)),
+        );
+        for (field, name) in names {
+            let key = name.trim().to_lowercase();
+            if key.is_empty() {
+                continue;
+            }
+            match taken.get(&key) {
+                Some(title) => problems.push(ConfigProblem::new(
+                    field,
+                    name,
+                    format!("The site {} already has this name", title),
+                    "Give it another name or remove it from the other site",
+                )),
+                None => {
+                    taken.insert(key, site.title.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Adds a site to search on, it replaces the ones that have any of its names or aliases
+///
+/// Does nothing if it's called after [`set_sites`]
+pub fn register_provider(provider: impl SearchProvider + 'static) {
+    PROVIDERS
+        .lock()
+        .expect("Couldn't lock PROVIDERS to register a provider")
+        .push(Box::new(provider));
+}
+
+pub fn set_sites() {
+    // The commands can't change after I start so neither can the sites they point to
+    let config: &'static BotConfig = Box::leak(Box::new(
+        BotConfig::get().expect("Couldn't get BOT_CONFIG to get the search sites"),
+    ));
+
+    let mut providers: Vec<&'static dyn SearchProvider> = std::mem::take(
+        &mut *PROVIDERS
+            .lock()
+            .expect("Couldn't lock PROVIDERS to set the sites"),
+    )
+    .into_iter()
+    .map(|provider| &*Box::leak(provider))
+    .collect();
+    providers.extend(
+        config
+            .sites()
+            .iter()
+            .map(|site| site as &'static dyn SearchProvider),
+    );
+
+    let mut sites: Vec<SearchSite> = Vec::new();
+    for provider in providers {
+        let info = provider.info();
 
-    match target {
-        Some((author, url)) => {
-            embed.description(url.as_str()).set_author(author.clone());
-            (embed, false)
+        let names: &'static [&'static str] = Box::leak(
+            info.names
+                .iter()
+                .map(|name| &*Box::leak(name.trim().to_lowercase().into_boxed_str()))
+                .collect::<Vec<&'static str>>()
+                .into_boxed_slice(),
+        );
+        if names.is_empty()
+            || names
+                .iter()
+                .any(|name| name.is_empty() || name.contains(' '))
+        {
+            panic!(
+                "The names of the site {} can't be empty or have spaces in them",
+                info.title
+            );
         }
+
+        let mut author = CreateEmbedAuthor::default();
+        author
+            .name(&info.title)
+            .url(&info.home)
+            .icon_url(&info.icon);
+
+        // A site replaces the ones before it that have any of its names
+        sites.retain(|site| !site.names.iter().any(|name| names.contains(name)));
+        sites.push(SearchSite {
+            names,
+            author,
+            provider,
+        });
+    }
+
+    let commands: Vec<&'static Command> = sites
+        .iter()
+        .map(|site| {
+            let info = site.provider.info();
+            let options: &'static CommandOptions = Box::leak(Box::new(CommandOptions {
+                bucket: Some(&*Box::leak(site.provider.bucket().into_boxed_str())),
+                names: site.names,
+                desc: Some(Box::leak(info.description.into_boxed_str())),
+                usage: info.usage.map(|usage| &*Box::leak(usage.into_boxed_str())),
+                examples: match info.example {
+                    Some(example) => Box::leak(Box::new([&*Box::leak(example.into_boxed_str())])),
+                    None => &[],
+                },
+                help_available: true,
+                checks: &SEARCH_CHECKS,
+                ..CommandOptions::default()
+            }));
+            &*Box::leak(Box::new(Command {
+                fun: cmd_search,
+                options,
+            }))
+        })
+        .collect();
+
+    SITES
+        .set(sites)
+        .unwrap_or_else(|_| panic!("Couldn't set the sites to SITES"));
+    SEARCH_GROUP
+        .set(CommandGroup {
+            name: "Search Things",
+            options: Box::leak(Box::new(GroupOptions {
+                help_available: true,
+                commands: Box::leak(commands.into_boxed_slice()),
+                ..GroupOptions::default()
+            })),
+        })
+        .unwrap_or_else(|_| panic!("Couldn't set the search group to SEARCH_GROUP"));
+}
+
+pub fn search_group() -> Option<&'static CommandGroup> {
+    SEARCH_GROUP.get()
+}
+
+fn invoked_site(msg: &Message, args: &Args) -> Option<&'static SearchSite> {
+    let content = msg.content.as_str();
+    let invoked = content
+        .get(..content.len().saturating_sub(args.message().len()))?
+        .trim_end()
+        .to_lowercase();
+
+    SITES
+        .get()?
+        .iter()
+        .flat_map(|site| site.names.iter().map(move |name| (site, name)))
+        .filter(|(_, name)| invoked.ends_with(*name))
+        .max_by_key(|(_, name)| name.len())
+        .map(|(site, _)| site)
+}
+
+/// The main name of the search command with this name or alias
+pub fn site_name(name: &str) -> Option<&'static str> {
+    SITES
+        .get()?
+        .iter()
+        .find(|site| site.names.contains(&name))
+        .map(|site| site.names[0])
+}
+
+/// The name of the bucket the search command with this name or alias uses
+pub fn site_bucket(name: &str) -> Option<String> {
+    SITES
+        .get()?
+        .iter()
+        .find(|site| site.names.contains(&name))
+        .map(|site| site.provider.bucket())
+}
+
+/// The name and description of every search command
+pub fn site_commands() -> Vec<(&'static str, String)> {
+    SITES
+        .get()
+        .map(|sites| {
+            sites
+                .iter()
+                .map(|site| (site.names[0], site.provider.info().description))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub async fn search_embeds(ctx: &Context, name: &str, term: &str) -> (Vec<CreateEmbed>, bool) {
+    let site = SITES
+        .get()
+        .and_then(|sites| sites.iter().find(|site| site.names.contains(&name)));
+    get_search_embeds(ctx, term, site).await
+}
+
+async fn get_search_embeds(
+    ctx: &Context,
+    term: &str,
+    site: Option<&SearchSite>,
+) -> (Vec<CreateEmbed>, bool) {
+    let mut embed = CreateEmbed::default();
+    let term = term.trim();
+
+    if term.is_empty() {
+        embed.title("I need something to search for though..");
+        return (vec![embed], true);
+    }
+
+    match site {
+        Some(site) => match site.provider.url(term) {
+            Some(url) => {
+                let mut pages = site.provider.pages(ctx, term, &url).await;
+                if !pages.is_empty() {
+                    for page in pages.iter_mut() {
+                        page.set_author(site.author.clone());
+                    }
+                    return (pages, false);
+                }
+
+                embed
+                    .description(url.as_str())
+                    .set_author(site.author.clone());
+                site.provider.preview(ctx, term, &mut embed).await;
+                (vec![embed], false)
+            }
+            None => {
+                embed
+                    .title("I can't make a link for that..")
+                    .description("Maybe try searching for something else?");
+                (vec![embed], true)
+            }
+        },
         None => {
             log(ctx, "Couldn't get the search site").await;
             embed.title("Ugh, I can't find where I kept all these search links");
-            (embed, true)
-        }
-    }
-}
-
-#[command("google")]
-#[aliases("s", "search")]
-#[bucket = "general"]
-#[description = "Let me help you google something"]
-#[usage = "[what you want me to google]"]
-#[example = "what's it like to feel emotions"]
-async fn cmd_google(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let (embed, is_error) = get_search_embed(ctx, args, GOOGLE.get(), false).await;
-    send_embed(ctx, msg, is_error, embed).await;
-    Ok(())
-}
-
-#[command("image")]
-#[aliases("i", "images", "imagesearch", "image-search", "image_search")]
-#[bucket = "general"]
-#[description = "Let me search Google images for you"]
-#[usage = "[what you want me to search google images for]"]
-#[example = "cute koalas"]
-async fn cmd_image(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let (embed, is_error) = get_search_embed(ctx, args, IMAGE.get(), false).await;
-    send_embed(ctx, msg, is_error, embed).await;
-    Ok(())
-}
-
-#[command("dictionary")]
-#[aliases("d", "wiktionary", "definition", "define", "meaning")]
-#[bucket = "general"]
-#[description = "Don't know a word? Let me help you look it up on Wiktionary for you\n(Wiktionary is like the Wikipedia of words and supports practically any language! It also has all sorts of info like pronunciation, etymology, examples etc. Seriously it's great)"]
-#[usage = "[what you don't know the definition of]"]
-#[example = "wie geht es dir"]
-async fn cmd_dictionary(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let (embed, is_error) = get_search_embed(ctx, args, DICTIONARY.get(), true).await;
-    send_embed(ctx, msg, is_error, embed).await;
-    Ok(())
-}
-
-#[command("urban")]
-#[aliases("u")]
-#[bucket = "general"]
-#[description = "Don't know the latest internet words and feeling like a boomer? Now I can help"]
-#[usage = "[the edgy phrase you want to learn]"]
-#[example = "third wheel"]
-async fn cmd_urban(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let (embed, is_error) = get_search_embed(ctx, args, URBAN.get(), false).await;
-    send_embed(ctx, msg, is_error, embed).await;
-    Ok(())
+            (vec![embed], true)
+        }
+    }
+}
+
+fn cmd_search<'fut>(
+    ctx: &'fut Context,
+    msg: &'fut Message,
+    args: Args,
+) -> BoxFuture<'fut, CommandResult> {
+    Box::pin(async move {
+        let site = invoked_site(msg, &args);
+        let (mut embeds, is_error) = get_search_embeds(ctx, args.rest(), site).await;
+        if embeds.len() > 1 {
+            send_pages(ctx, msg, embeds).await;
+        } else if let Some(embed) = embeds.pop() {
+            send_embed(ctx, msg, is_error, embed).await;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOGLE: &str = "https://www.google.com/search";
+    const WIKTIONARY: &str = "https://en.wiktionary.org";
+
+    #[test]
+    fn encodes_tricky_terms() {
+        // The term, then how it looks in a query and in a path
+        let cases = [
+            ("AT&T stock", "AT%26T+stock", Some("AT&T%20stock")),
+            ("and/or", "and%2For", Some("and%2For")),
+            ("c++ #1?", "c%2B%2B+%231%3F", Some("c++%20%231%3F")),
+            ("100%", "100%25", Some("100%25")),
+            (".", ".", None),
+            ("..", "..", None),
+            ("...", "...", Some("...")),
+            (
+                "日本語",
+                "%E6%97%A5%E6%9C%AC%E8%AA%9E",
+                Some("%E6%97%A5%E6%9C%AC%E8%AA%9E"),
+            ),
+            (
+                "привет мир",
+                "%D0%BF%D1%80%D0%B8%D0%B2%D0%B5%D1%82+%D0%BC%D0%B8%D1%80",
+                Some("%D0%BF%D1%80%D0%B8%D0%B2%D0%B5%D1%82%20%D0%BC%D0%B8%D1%80"),
+            ),
+            (
+                "مرحبا",
+                "%D9%85%D8%B1%D8%AD%D8%A8%D8%A7",
+                Some("%D9%85%D8%B1%D8%AD%D8%A8%D8%A7"),
+            ),
+        ];
+
+        for (term, query, path) in cases.iter() {
+            assert_eq!(encode_query(term), *query, "encode_query({:?})", term);
+            assert_eq!(
+                encode_path(term).as_deref(),
+                *path,
+                "encode_path({:?})",
+                term
+            );
+
+            assert_eq!(
+                query_url(GOOGLE, "q={}", term).map(String::from),
+                Some(format!("{}?q={}", GOOGLE, query)),
+                "query_url({:?})",
+                term
+            );
+            assert_eq!(
+                path_url(WIKTIONARY, "wiki/{}", term).map(String::from),
+                path.map(|path| format!("{}/wiki/{}", WIKTIONARY, path)),
+                "path_url({:?})",
+                term
+            );
+            assert_eq!(
+                template_url("https://duckduckgo.com/?q={}", term).map(String::from),
+                Some(format!("https://duckduckgo.com/?q={}", query)),
+                "template_url({:?}) in the query",
+                term
+            );
+            assert_eq!(
+                template_url("https://example.com/wiki/{}", term).map(String::from),
+                path.map(|path| format!("https://example.com/wiki/{}", path)),
+                "template_url({:?}) in the path",
+                term
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_query_in_the_base() {
+        let cases = [
+            (
+                "https://example.com/search?lang=en",
+                "q={}",
+                "c++ #1?",
+                "https://example.com/search?lang=en&q=c%2B%2B+%231%3F",
+            ),
+            (
+                "https://example.com/search?",
+                "q={}",
+                "AT&T stock",
+                "https://example.com/search?q=AT%26T+stock",
+            ),
+            (
+                GOOGLE,
+                "tbm=isch&q={}",
+                "100%",
+                "https://www.google.com/search?tbm=isch&q=100%25",
+            ),
+        ];
+
+        for (base, template, term, url) in cases.iter() {
+            assert_eq!(
+                query_url(base, template, term).map(String::from).as_deref(),
+                Some(*url),
+                "query_url({:?}, {:?}, {:?})",
+                base,
+                template,
+                term
+            );
+        }
+        assert_eq!(
+            template_url("https://example.com/search?lang=en&q={}", "and/or")
+                .map(String::from)
+                .as_deref(),
+            Some("https://example.com/search?lang=en&q=and%2For")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_site_names() {
+        // The names, then the fields with problems
+        let cases: [(&str, &str, &[&str]); 6] = [
+            ("duckduckgo", r#""ddg", "duck""#, &[]),
+            ("duck duck", r#""ddg""#, &["sites[0].name"]),
+            ("duckduckgo", r#""ddg", """#, &["sites[0].aliases[1]"]),
+            (
+                "duckduckgo",
+                r#"" ", "d\tg""#,
+                &["sites[0].aliases[0]", "sites[0].aliases[1]"],
+            ),
+            ("Help", r#""ddg""#, &["sites[0].name"]),
+            (
+                "duckduckgo",
+                r#""info", "prefix", "site""#,
+                &[
+                    "sites[0].aliases[0]",
+                    "sites[0].aliases[1]",
+                    "sites[0].aliases[2]",
+                ],
+            ),
+        ];
+
+        for (name, aliases, fields) in cases.iter() {
+            let site: Site = toml::from_str(&format!(
+                r#"name = "{}"
+                aliases = [{}]
+                title = "DuckDuckGo"
+                url = "https://duckduckgo.com/"
+                template = "q={{}}"
+                mode = "query"
+                icon = "https://duckduckgo.com/favicon.ico"
+                description = "Let me search DuckDuckGo for you""#,
+                name, aliases
+            ))
+            .expect("Couldn't parse the site");
+            let mut problems = Vec::new();
+            site.validate("sites[0]", &HashMap::new(), &mut problems);
+
+            let problem_fields: Vec<&str> = problems
+                .iter()
+                .map(|problem| problem.field.as_str())
+                .collect();
+            assert_eq!(problem_fields, *fields, "{:?} with {}", name, aliases);
+        }
+    }
+
+    #[test]
+    fn rejects_names_other_sites_have() {
+        let site = |name: &str, aliases: &str| -> Site {
+            toml::from_str(&format!(
+                r#"name = "{}"
+                aliases = [{}]
+                title = "{}"
+                url = "https://duckduckgo.com/"
+                template = "q={{}}"
+                mode = "query"
+                icon = "https://duckduckgo.com/favicon.ico"
+                description = "Let me search it for you""#,
+                name, aliases, name
+            ))
+            .expect("Couldn't parse the site")
+        };
+        // The sites, then the fields with problems
+        let cases: [(Vec<Site>, &[&str]); 4] = [
+            (vec![site("duckduckgo", r#""ddg""#)], &[]),
+            (
+                vec![site("duckduckgo", r#""d", "Search""#)],
+                &["sites[0].aliases[0]", "sites[0].aliases[1]"],
+            ),
+            // It replaces Google so the names of Google are free
+            (vec![site("google", r#""s", "g""#)], &[]),
+            (
+                vec![site("duckduckgo", r#""duck""#), site("duck", r#""ddg""#)],
+                &["sites[1].name"],
+            ),
+        ];
+
+        for (sites, fields) in cases.iter() {
+            let mut problems = Vec::new();
+            validate_site_names(sites, &mut problems);
+
+            let problem_fields: Vec<&str> = problems
+                .iter()
+                .map(|problem| problem.field.as_str())
+                .collect();
+            assert_eq!(problem_fields, *fields, "{:?}", fields);
+        }
+    }
 }
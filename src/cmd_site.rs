@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serenity::{
+    builder::{CreateEmbed, CreateEmbedAuthor},
+    client::Context,
+    framework::standard::{
+        macros::{command, hook},
+        Args, CommandResult, Reason,
+    },
+    model::{channel::Message, id::GuildId},
+};
+use sqlx::{query, AnyPool, Row};
+use url::Url;
+
+use crate::{
+    cmd_block::is_blocked, cmd_bucket::take_bucket, cmd_channel::is_cmd_disabled,
+    cmd_prefix::prefix_check, cmd_search::template_url, globals::CmdInfo, guild_and_db, log,
+    send_embed,
+};
+
+const MAX_SITES: i64 = 25;
+const IN_DMS: &str = "We have to be in a guild to change its sites, no?";
+
+struct GuildSite {
+    url: String,
+    icon: Option<String>,
+}
+
+static GUILD_SITES: Lazy<DashMap<GuildId, HashMap<String, GuildSite>>> = Lazy::new(DashMap::new);
+
+pub async fn load_guild_sites(db: &AnyPool) {
+    let rows = query("SELECT guild_id, name, url, icon FROM guild_sites")
+        .fetch_all(db)
+        .await
+        .expect("Couldn't load the guild sites");
+
+    for row in rows.iter() {
+        let guild_id: i64 = row
+            .try_get(0)
+            .expect("Couldn't get a guild_id from guild_sites");
+        GUILD_SITES
+            .entry(GuildId(guild_id as u64))
+            .or_default()
+            .insert(
+                row.try_get(1)
+                    .expect("Couldn't get a name from guild_sites"),
+                GuildSite {
+                    url: row.try_get(2).expect("Couldn't get a url from guild_sites"),
+                    icon: row
+                        .try_get(3)
+                        .expect("Couldn't get an icon from guild_sites"),
+                },
+            );
+    }
+}
+
+pub fn forget_guild_sites(guild_id: GuildId) {
+    GUILD_SITES.remove(&guild_id);
+}
+
+pub fn has_guild_site(guild_id: GuildId, name: &str) -> bool {
+    GUILD_SITES
+        .get(&guild_id)
+        .is_some_and(|sites| sites.contains_key(&name.to_lowercase()))
+}
+
+#[command("site")]
+#[aliases("sites", "customsite", "custom_site", "custom-site")]
+#[sub_commands(cmd_site_add, cmd_site_remove, cmd_site_list)]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[description = "Add your own search commands to this server, like one for your wiki\nThey work just like the ones in `Search Things`"]
+#[usage = "[add, remove or list]"]
+#[example = "list"]
+async fn cmd_site(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("Do you want to add, remove or list the sites?")
+        .description("Type `help site` if you don't know how");
+    send_embed(ctx, msg, true, embed).await;
+    Ok(())
+}
+
+#[command("add")]
+#[aliases("set", "new")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Add a search command to this server\n`{}` in the link is replaced with what you search for and you can add a link to an icon after it"]
+#[usage = "[name] [link] [icon link]"]
+#[example = "wiki https://wiki.example.com/search?q={}"]
+async fn cmd_site_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+
+    let name = args.single::<String>().unwrap_or_default().to_lowercase();
+    let url = args.single::<String>().unwrap_or_default();
+    let icon = args.single::<String>().ok();
+
+    if let Some((guild_id, db)) = guild_and_db(ctx, msg.guild_id, &mut embed, "site", IN_DMS).await
+    {
+        if name.is_empty() || url.is_empty() {
+            embed
+                .title("I need a name and a link for the site")
+                .description("Type `help site add` if you don't know how");
+        } else if name.chars().count() > 20
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            embed
+                .title("The name can only have letters, numbers, - and _ in it")
+                .description("And it can't be longer than 20 characters");
+        } else if CmdInfo::get().is_some_and(|info| info.is_cmd(&name)) {
+            embed.title(format!("I already have a command named `{}`", name));
+        } else if !url.contains("{}") {
+            embed
+                .title("Where do I put what you search for in that link?")
+                .description("Put `{}` in the link where it should go");
+        } else if let Err(err) = Url::parse(&url.replace("{}", "test")) {
+            embed
+                .title("That doesn't look like a link to me")
+                .description(err);
+        } else if Url::parse(&url.replace("{}", "test"))
+            .is_ok_and(|url| !matches!(url.scheme(), "http" | "https"))
+        {
+            embed
+                .title("The link has to start with http:// or https://")
+                .description("I can only send people to websites");
+        } else if let Some(Err(err)) = icon.as_ref().map(|icon| Url::parse(icon)) {
+            embed
+                .title("That doesn't look like a link to an icon to me")
+                .description(err);
+        } else {
+            match query("SELECT COUNT(*) FROM guild_sites WHERE guild_id = $1 AND name != $2")
+                .bind(guild_id.0 as i64)
+                .bind(&name)
+                .fetch_one(&db)
+                .await
+                .and_then(|row| row.try_get::<i64, _>(0))
+            {
+                Err(err) => {
+                    log(ctx, format!("Couldn't count the guild's sites: {}", err)).await;
+                    embed
+                        .title("Ugh, I couldn't look at the sites you have..")
+                        .description(
+                            "I just let my developer know, until then you could just try again",
+                        );
+                }
+                Ok(count) if count >= MAX_SITES => {
+                    embed
+                        .title(format!("You can't have more than {} sites", MAX_SITES))
+                        .description("Remove some with `site remove` first");
+                }
+                Ok(_) => {
+                    if let Err(err) = query(
+                        "INSERT INTO guild_sites (guild_id, name, url, icon)
+                        VALUES($1, $2, $3, $4)
+                        ON CONFLICT (guild_id, name) DO UPDATE
+                        SET url = excluded.url, icon = excluded.icon;",
+                    )
+                    .bind(guild_id.0 as i64)
+                    .bind(&name)
+                    .bind(&url)
+                    .bind(&icon)
+                    .execute(&db)
+                    .await
+                    {
+                        log(ctx, format!("Couldn't insert to guild_sites: {}", err)).await;
+                        embed
+                            .title("Ugh, I couldn't write that down..")
+                            .description(
+                                "I just let my developer know, until then you could just try again",
+                            );
+                    } else {
+                        GUILD_SITES.entry(guild_id).or_default().insert(
+                            name.clone(),
+                            GuildSite {
+                                url: url.clone(),
+                                icon: icon.clone(),
+                            },
+                        );
+                        is_error = false;
+                        embed
+                            .description(format!("Voila! You can now search with `{}` here", name));
+                    }
+                }
+            }
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[command("remove")]
+#[aliases("delete", "rm", "del")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "expensive"]
+#[description = "Remove a search command you added to this server"]
+#[usage = "[name]"]
+#[example = "wiki"]
+async fn cmd_site_remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+    let name = args.rest().trim().to_lowercase();
+
+    if let Some((guild_id, db)) = guild_and_db(ctx, msg.guild_id, &mut embed, "site", IN_DMS).await
+    {
+        match query("DELETE FROM guild_sites WHERE guild_id = $1 AND name = $2")
+            .bind(guild_id.0 as i64)
+            .bind(&name)
+            .execute(&db)
+            .await
+        {
+            Err(err) => {
+                log(ctx, format!("Couldn't delete from guild_sites: {}", err)).await;
+                embed.title("Ugh, I couldn't erase that..").description(
+                    "I just let my developer know, until then you could just try again",
+                );
+            }
+            Ok(done) if done.rows_affected() == 0 => {
+                embed
+                    .title(format!("There's no site named `{}` here", name))
+                    .description("Type `site list` to see the sites you added");
+            }
+            Ok(_) => {
+                if let Some(mut sites) = GUILD_SITES.get_mut(&guild_id) {
+                    sites.remove(&name);
+                }
+                is_error = false;
+                embed.description(format!("Poof! `{}` is gone", name));
+            }
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[command("list")]
+#[aliases("all", "ls")]
+#[required_permissions("MANAGE_GUILD")]
+#[only_in("guilds")]
+#[bucket = "general"]
+#[description = "See the search commands added to this server"]
+async fn cmd_site_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut embed = CreateEmbed::default();
+    let mut is_error = true;
+
+    if let Some((guild_id, db)) = guild_and_db(ctx, msg.guild_id, &mut embed, "site", IN_DMS).await
+    {
+        match query("SELECT name, url FROM guild_sites WHERE guild_id = $1 ORDER BY name")
+            .bind(guild_id.0 as i64)
+            .fetch_all(&db)
+            .await
+        {
+            Err(err) => {
+                log(ctx, format!("Couldn't fetch from guild_sites: {}", err)).await;
+                embed.title("Ugh, I couldn't read my notes..").description(
+                    "I just let my developer know, until then you could just try again",
+                );
+            }
+            Ok(rows) if rows.is_empty() => {
+                is_error = false;
+                embed
+                    .title("No sites added here yet")
+                    .description("Add one with `site add`");
+            }
+            Ok(rows) => {
+                is_error = false;
+                embed.title("The sites added here");
+                for row in rows.iter() {
+                    embed.field(
+                        row.try_get::<String, _>(0).unwrap_or_default(),
+                        row.try_get::<String, _>(1).unwrap_or_default(),
+                        false,
+                    );
+                }
+            }
+        }
+    }
+
+    send_embed(ctx, msg, is_error, embed).await;
+    Ok(())
+}
+
+#[hook]
+pub async fn guild_site(ctx: &Context, msg: &Message, name: &str) {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let name = name.to_lowercase();
+    if is_cmd_disabled(msg.channel_id, &name)
+        || is_blocked(msg.author.id, msg.guild_id, msg.channel_id)
+    {
+        return;
+    }
+
+    let (template, icon) = match GUILD_SITES.get(&guild_id).and_then(|sites| {
+        sites
+            .get(&name)
+            .map(|site| (site.url.clone(), site.icon.clone()))
+    }) {
+        Some(site) => site,
+        None => return,
+    };
+
+    let mut embed = CreateEmbed::default();
+    // Like the search commands
+    match take_bucket(
+        ctx,
+        "general",
+        msg.author.id,
+        msg.channel_id,
+        msg.guild_id,
+        Some(msg),
+    )
+    .await
+    {
+        Ok(()) => {}
+        Err(Reason::User(text)) => {
+            embed.description(text);
+            send_embed(ctx, msg, true, embed).await;
+            return;
+        }
+        Err(_) => return,
+    }
+
+    // The command's name is the first word after the prefix
+    let term = msg.content[prefix_len(ctx, msg).await..]
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .map_or("", |(_, term)| term.trim());
+
+    let is_error = if term.is_empty() {
+        embed.title("I need something to search for though..");
+        true
+    } else {
+        match template_url(&template, term) {
+            Some(url) => {
+                let mut author = CreateEmbedAuthor::default();
+                author
+                    .name(&name)
+                    .url(url.join("/").unwrap_or_else(|_| url.clone()));
+                if let Some(icon) = icon {
+                    author.icon_url(icon);
+                }
+                embed.description(url.as_str()).set_author(author);
+                false
+            }
+            None => {
+                embed
+                    .title("Looks like the link for this site is broken")
+                    .description("Ask the admins to fix it with `site add`");
+                true
+            }
+        }
+    };
+
+    send_embed(ctx, msg, is_error, embed).await;
+}
+
+/// How long the prefix of the message is, found the same way the framework does
+async fn prefix_len(ctx: &Context, msg: &Message) -> usize {
+    let content = msg.content.as_str();
+
+    if let Some(rest) = content.strip_prefix("<@") {
+        let rest = rest.strip_prefix('!').unwrap_or(rest);
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits > 0 && rest[digits..].starts_with('>') {
+            return content.len() - rest.len() + digits + 1;
+        }
+    }
+
+    match prefix_check(ctx, msg).await {
+        Some(prefix) => {
+            let end = content
+                .char_indices()
+                .nth(prefix.chars().count())
+                .map_or(content.len(), |(index, _)| index);
+            if content[..end].to_lowercase() == prefix.to_lowercase() {
+                end
+            } else {
+                0
+            }
+        }
+        None => 0,
+    }
+}
@@ -0,0 +1,262 @@
+use serenity::{
+    builder::{CreateApplicationCommands, CreateEmbed},
+    client::Context,
+    framework::standard::Reason,
+    model::{
+        id::GuildId,
+        interactions::{
+            application_command::{
+                ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandOptionType,
+            },
+            Interaction, InteractionResponseType,
+        },
+    },
+};
+
+use crate::{
+    cmd_block::blocked_reason, cmd_bucket::take_bucket, cmd_channel::is_cmd_disabled,
+    cmd_info::info_embed, cmd_prefix::set_prefix, cmd_search, definitions::truncate,
+    globals::BotConfig, log, number_pages, set_colour, turn_pages,
+};
+
+pub async fn register(ctx: &Context) {
+    let test_guild = match BotConfig::get() {
+        Some(config) => config.slash_test_guild(),
+        None => {
+            log(ctx, "Couldn't get BotConfig to register the slash commands").await;
+            return;
+        }
+    };
+
+    let result = match test_guild {
+        Some(guild_id) => GuildId(guild_id)
+            .set_application_commands(&ctx.http, create_commands)
+            .await
+            .map(|_| ()),
+        None => ApplicationCommand::set_global_application_commands(&ctx.http, create_commands)
+            .await
+            .map(|_| ()),
+    };
+    if let Err(err) = result {
+        log(
+            ctx,
+            format!("Couldn't register the slash commands: {}", err),
+        )
+        .await;
+    }
+}
+
+fn create_commands(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    for (name, description) in cmd_search::site_commands() {
+        if name.chars().count() > 32
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            continue;
+        }
+
+        commands.create_application_command(|c| {
+            c.name(name)
+                .description(truncate(description.lines().next().unwrap_or(name), 100))
+                .create_option(|o| {
+                    o.name("term")
+                        .description("What you want me to search for")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                })
+        });
+    }
+
+    commands
+        .create_application_command(|c| {
+            c.name("info").description(
+                "How you can add me to your server, contact my owner, find my GitHub page etc.",
+            )
+        })
+        .create_application_command(|c| {
+            c.name("prefix")
+                .description("Change the prefix I'll use in this server")
+                .create_option(|o| {
+                    o.name("prefix")
+                        .description("Your prefix, leave it empty if you don't want one")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(false)
+                })
+        })
+}
+
+fn option_str<'a>(command: &'a ApplicationCommandInteraction, name: &str) -> Option<&'a str> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)?
+        .value
+        .as_ref()?
+        .as_str()
+}
+
+pub async fn handle(ctx: &Context, interaction: Interaction) {
+    let command = match interaction {
+        Interaction::ApplicationCommand(command) => command,
+        _ => return,
+    };
+
+    if let Err(err) = command
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+        })
+        .await
+    {
+        log(
+            ctx,
+            format!("Couldn't respond to the slash command: {}", err),
+        )
+        .await;
+        return;
+    }
+
+    let blocked = blocked_reason(command.user.id, command.guild_id, command.channel_id);
+    // The same buckets as the commands in messages
+    let bucket = match command.data.name.as_str() {
+        "info" => Some("general".to_string()),
+        "prefix" => Some("expensive".to_string()),
+        name if is_cmd_disabled(command.channel_id, name) => None,
+        name => cmd_search::site_bucket(name),
+    };
+    let limited = match bucket {
+        Some(bucket) if blocked.is_none() => take_bucket(
+            ctx,
+            &bucket,
+            command.user.id,
+            command.channel_id,
+            command.guild_id,
+            None,
+        )
+        .await
+        .err(),
+        _ => None,
+    };
+
+    let (mut embeds, is_error) = match command.data.name.as_str() {
+        _ if blocked.is_some() || limited.is_some() => {
+            match blocked.or(limited).unwrap_or(Reason::Unknown) {
+                Reason::User(text) => {
+                    let mut embed = CreateEmbed::default();
+                    embed.description(text);
+                    (vec![embed], true)
+                }
+                // They were told already, so only the thinking message goes away
+                _ => {
+                    if let Err(err) = command
+                        .delete_original_interaction_response(&ctx.http)
+                        .await
+                    {
+                        log(
+                            ctx,
+                            format!("Couldn't delete the slash command's response: {}", err),
+                        )
+                        .await;
+                    }
+                    return;
+                }
+            }
+        }
+        "info" => {
+            let (embed, is_error) = info_embed(ctx).await;
+            (vec![embed], is_error)
+        }
+        "prefix" => {
+            let can_manage = command
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.manage_guild());
+
+            if command.guild_id.is_none() {
+                let mut embed = CreateEmbed::default();
+                embed.description("You can only use this command in a guild 😳");
+                (vec![embed], true)
+            } else if !can_manage {
+                let mut embed = CreateEmbed::default();
+                embed.description(
+                    "**You need these permissions to run this command and you don't have them** 😤\nManage Server",
+                );
+                (vec![embed], true)
+            } else {
+                let prefix = option_str(&command, "prefix").unwrap_or_default();
+                let (embed, is_error) = set_prefix(ctx, command.guild_id, prefix).await;
+                (vec![embed], is_error)
+            }
+        }
+        name if is_cmd_disabled(command.channel_id, name) => {
+            let mut embed = CreateEmbed::default();
+            embed.description("The admins turned this command off in this channel");
+            (vec![embed], true)
+        }
+        name => {
+            let term = option_str(&command, "term").unwrap_or_default();
+            cmd_search::search_embeds(ctx, name, term).await
+        }
+    };
+
+    if embeds.len() > 1 {
+        number_pages(&mut embeds);
+    } else {
+        for embed in embeds.iter_mut() {
+            set_colour(ctx, embed, is_error).await;
+        }
+    }
+
+    let first = match embeds.first() {
+        Some(first) => first.clone(),
+        None => return,
+    };
+    if let Err(err) = command
+        .edit_original_interaction_response(&ctx.http, |r| r.add_embed(first))
+        .await
+    {
+        log(
+            ctx,
+            format!("Couldn't send the slash command's response: {}", err),
+        )
+        .await;
+        return;
+    }
+    if embeds.len() < 2 {
+        return;
+    }
+
+    let sent = match command.get_interaction_response(&ctx.http).await {
+        Ok(sent) => sent,
+        Err(err) => {
+            log(
+                ctx,
+                format!("Couldn't get the slash command's response: {}", err),
+            )
+            .await;
+            return;
+        }
+    };
+    if let Err(err) = turn_pages(ctx, sent, command.user.id, embeds).await {
+        let mut embed = CreateEmbed::default();
+        embed
+            .title("I couldn't add the reactions to turn the pages..")
+            .description(format!(
+                "{}\nLet the admins know so they can let me add reactions",
+                err
+            ));
+        set_colour(ctx, &mut embed, true).await;
+        if let Err(err) = command
+            .create_followup_message(&ctx.http, |m| m.add_embed(embed))
+            .await
+        {
+            log(
+                ctx,
+                format!("Couldn't send the slash command's followup: {}", err),
+            )
+            .await;
+        }
+    }
+}
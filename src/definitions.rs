@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serenity::{builder::CreateEmbed, client::Context};
+
+use crate::{cmd_search::encode_path, globals::BotConfig, log};
+
+const MAX_PARTS: usize = 3;
+const MAX_DEFINITIONS: usize = 3;
+const MAX_PAGES: usize = 10;
+const MAX_FIELD_LEN: usize = 1024;
+/// The most characters Discord shows in an embed's description
+pub const MAX_DESCRIPTION_LEN: usize = 4096;
+
+pub static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .user_agent(concat!(
+            "discord-search/",
+            env!("CARGO_PKG_VERSION"),
+            " (https://github.com/aria-7553/discord-search)"
+        ))
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .expect("Couldn't build the HTTP client")
+});
+
+#[derive(Deserialize)]
+struct WiktionaryPart {
+    #[serde(rename = "partOfSpeech")]
+    part_of_speech: String,
+    language: String,
+    #[serde(default)]
+    definitions: Vec<WiktionaryDefinition>,
+}
+
+#[derive(Deserialize)]
+struct WiktionaryDefinition {
+    definition: String,
+    #[serde(default)]
+    examples: Vec<String>,
+}
+
+pub async fn wiktionary(ctx: &Context, term: &str, embed: &mut CreateEmbed) {
+    let base = match BotConfig::get() {
+        Some(config) => config.wiktionary_api().clone(),
+        None => {
+            log(ctx, "Couldn't get BotConfig to get the Wiktionary API").await;
+            return;
+        }
+    };
+    let term = match encode_path(term) {
+        Some(term) => term,
+        None => return,
+    };
+
+    let parts = match fetch_wiktionary(&base, &term).await {
+        Ok(Some(parts)) => parts,
+        Ok(None) => return,
+        Err(err) => {
+            log(
+                ctx,
+                format!("Couldn't get the definitions from Wiktionary: {}", err),
+            )
+            .await;
+            return;
+        }
+    };
+
+    for (name, value) in wiktionary_fields(&parts) {
+        embed.field(name, value, false);
+    }
+}
+
+/// The name and the definitions of each part of speech that has any, up to `MAX_PARTS` of them
+fn wiktionary_fields(parts: &[WiktionaryPart]) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    for part in parts.iter().take(MAX_PARTS) {
+        let value = part
+            .definitions
+            .iter()
+            .map(|definition| (strip_html(&definition.definition), &definition.examples))
+            .filter(|(definition, _)| !definition.is_empty())
+            .take(MAX_DEFINITIONS)
+            .enumerate()
+            .map(|(i, (definition, examples))| match examples.first() {
+                Some(example) => format!("{}. {}\n> *{}*", i + 1, definition, strip_html(example)),
+                None => format!("{}. {}", i + 1, definition),
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        if !value.is_empty() {
+            fields.push((
+                format!("{} ({})", part.part_of_speech, part.language),
+                truncate(&value, MAX_FIELD_LEN),
+            ));
+        }
+    }
+    fields
+}
+
+async fn fetch_wiktionary(
+    base: &str,
+    term: &str,
+) -> Result<Option<Vec<WiktionaryPart>>, reqwest::Error> {
+    let response = CLIENT
+        .get(format!(
+            "{}/page/definition/{}",
+            base.trim_end_matches('/'),
+            term
+        ))
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let mut languages = response
+        .error_for_status()?
+        .json::<BTreeMap<String, Vec<WiktionaryPart>>>()
+        .await?;
+
+    let mut parts = languages.remove("en").unwrap_or_default();
+    parts.extend(languages.into_values().flatten());
+    Ok(Some(parts))
+}
+
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+pub fn truncate(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(max_len - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[derive(Deserialize)]
+struct UrbanResponse {
+    list: Vec<UrbanDefinition>,
+}
+
+#[derive(Deserialize)]
+struct UrbanDefinition {
+    word: String,
+    definition: String,
+    #[serde(default)]
+    example: String,
+    thumbs_up: u32,
+    thumbs_down: u32,
+}
+
+pub async fn urban(ctx: &Context, term: &str, link: &str) -> Vec<CreateEmbed> {
+    let base = match BotConfig::get() {
+        Some(config) => config.urban_api().clone(),
+        None => {
+            log(
+                ctx,
+                "Couldn't get BotConfig to get the Urban Dictionary API",
+            )
+            .await;
+            return Vec::new();
+        }
+    };
+
+    let definitions = match fetch_urban(&base, term).await {
+        Ok(definitions) => definitions,
+        Err(err) => {
+            log(
+                ctx,
+                format!(
+                    "Couldn't get the definitions from Urban Dictionary: {}",
+                    err
+                ),
+            )
+            .await;
+            return Vec::new();
+        }
+    };
+
+    definitions
+        .iter()
+        .take(MAX_PAGES)
+        .map(|definition| {
+            let mut embed = CreateEmbed::default();
+            embed
+                .title(&definition.word)
+                .url(link)
+                .description(truncate(
+                    &strip_brackets(&definition.definition),
+                    MAX_DESCRIPTION_LEN,
+                ))
+                .field("👍", definition.thumbs_up, true)
+                .field("👎", definition.thumbs_down, true);
+            let example = strip_brackets(&definition.example);
+            if !example.is_empty() {
+                embed.field("Example", truncate(&example, MAX_FIELD_LEN), false);
+            }
+            embed
+        })
+        .collect()
+}
+
+async fn fetch_urban(base: &str, term: &str) -> Result<Vec<UrbanDefinition>, reqwest::Error> {
+    Ok(CLIENT
+        .get(format!("{}/define", base.trim_end_matches('/')))
+        .query(&[("term", term)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<UrbanResponse>()
+        .await?
+        .list)
+}
+
+fn strip_brackets(text: &str) -> String {
+    text.replace(['[', ']'], "").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    const DEFINITION: &str = include_str!("../tests/fixtures/wiktionary_definition.json");
+    const NOT_FOUND: &str = r#"{"type":"https://mediawiki.org/wiki/HyperSwitch/errors/not_found","title":"Not found.","method":"get","detail":"Page or revision not found."}"#;
+
+    /// Serves the fixture for `cat` like the Wiktionary API and 404s for everything else,
+    /// returning the base link to use instead of `wiktionary_api`
+    fn mock_wiktionary() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind the mock server");
+        let base = format!(
+            "http://{}/api/rest_v1/",
+            listener.local_addr().expect("Couldn't get its address")
+        );
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.expect("Couldn't accept a connection");
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buf[..read]),
+                    }
+                }
+
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                let (status, body) = if path == "/api/rest_v1/page/definition/cat" {
+                    ("200 OK", DEFINITION)
+                } else {
+                    ("404 Not Found", NOT_FOUND)
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn reads_the_definitions() {
+        let parts = fetch_wiktionary(&mock_wiktionary(), "cat")
+            .await
+            .expect("Couldn't get the definitions")
+            .expect("The fixture wasn't found");
+
+        // English first even though German comes before it in the response
+        let languages: Vec<&str> = parts.iter().map(|part| part.language.as_str()).collect();
+        assert_eq!(languages, ["English", "English", "German", "French"]);
+
+        let fields = wiktionary_fields(&parts);
+        assert_eq!(fields.len(), MAX_PARTS);
+        assert_eq!(
+            fields[0],
+            (
+                "Noun (English)".to_string(),
+                "1. An animal of the family Felidae:\n> *The cat sat on the mat & purred.*\n2. (slang) A person, especially a \"cool\" one.\n3. A catfish <fish>.".to_string()
+            )
+        );
+        assert_eq!(
+            fields[1],
+            (
+                "Verb (English)".to_string(),
+                "1. transitive To hoist (the anchor) by its ring.\n> *They catted the anchor.*"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            fields[2],
+            (
+                "Noun (German)".to_string(),
+                "1. inflection of Katze".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn has_no_fields_when_not_found() {
+        let parts = fetch_wiktionary(&mock_wiktionary(), "qwertyuiop")
+            .await
+            .expect("Couldn't ask for the definitions");
+        assert!(parts.is_none());
+    }
+
+    #[test]
+    fn strips_html() {
+        let cases = [
+            ("<i>plain</i>", "plain"),
+            ("a &lt;b&gt; &quot;c&quot; &#39;d&#39;", "a <b> \"c\" 'd'"),
+            ("&amp;lt;", "&lt;"),
+            ("one&nbsp;two", "one two"),
+            ("<span></span>", ""),
+            ("  <b>x</b> > y  ", "x > y"),
+        ];
+        for (html, text) in cases.iter() {
+            assert_eq!(strip_html(html), *text, "strip_html({:?})", html);
+        }
+    }
+}
@@ -1,63 +1,192 @@
-use std::{env, fmt::Display, io::Write};
+use std::{env, fmt::Display, fs, path::Path, time::Duration};
 
 use serenity::{
     builder::CreateEmbed,
     client::{Context, EventHandler},
     framework::standard::macros::group,
+    futures::StreamExt,
     model::{
-        channel::Message,
-        id::GuildId,
+        channel::{Message, ReactionType},
+        guild::{Guild, GuildUnavailable},
+        id::{GuildId, UserId},
+        interactions::Interaction,
         misc::Mentionable,
-        prelude::{Activity, Ready},
+        prelude::Ready,
     },
 };
 
-use globals::{BotConfig, BotInfo};
+use globals::{BotConfig, DatabaseKey};
+use logging::Level;
+use sqlx::AnyPool;
 
 use crate::{
+    cmd_admin::{
+        CMD_GUILDS_COMMAND, CMD_LEAVE_COMMAND, CMD_SETACTIVITY_COMMAND, CMD_SHUTDOWN_COMMAND,
+        CMD_SQL_COMMAND, CMD_STATS_COMMAND,
+    },
+    cmd_block::{CMD_BLOCKLIST_COMMAND, CMD_BLOCK_COMMAND, CMD_UNBLOCK_COMMAND, NOT_BLOCKED_CHECK},
+    cmd_bucket::{CMD_BUCKET_COMMAND, RATE_LIMIT_CHECK},
+    cmd_channel::CMD_CHANNEL_COMMAND,
     cmd_info::CMD_INFO_COMMAND,
     cmd_prefix::CMD_PREFIX_COMMAND,
-    cmd_search::{
-        CMD_DICTIONARY_COMMAND, CMD_GOOGLE_COMMAND, CMD_IMAGE_COMMAND, CMD_URBAN_COMMAND,
-    },
+    cmd_purge::CMD_PURGE_COMMAND,
+    cmd_reload::CMD_RELOAD_COMMAND,
+    cmd_site::CMD_SITE_COMMAND,
 };
 
+pub mod cli;
+pub mod cmd_admin;
+pub mod cmd_block;
+pub mod cmd_bucket;
+pub mod cmd_channel;
 pub mod cmd_error;
 pub mod cmd_help;
 pub mod cmd_info;
 pub mod cmd_prefix;
+pub mod cmd_purge;
+pub mod cmd_reload;
 pub mod cmd_search;
+pub mod cmd_site;
+pub mod cmd_slash;
+pub mod definitions;
 pub mod globals;
-
-#[group("Master")]
-#[sub_groups(General, Search)]
-#[help_available(false)]
-struct Master;
+pub mod logging;
+pub mod migrations;
 
 #[group("General Stuff")]
-#[commands(cmd_info, cmd_prefix)]
+#[commands(cmd_info, cmd_prefix, cmd_site, cmd_channel)]
+#[checks(not_blocked, rate_limit)]
 struct General;
 
-#[group("Search Things")]
-#[commands(cmd_google, cmd_image, cmd_dictionary, cmd_urban)]
-struct Search;
+#[group("Owner Stuff")]
+#[owners_only]
+#[help_available(false)]
+#[commands(
+    cmd_stats,
+    cmd_guilds,
+    cmd_leave,
+    cmd_shutdown,
+    cmd_setactivity,
+    cmd_sql,
+    cmd_block,
+    cmd_unblock,
+    cmd_blocklist,
+    cmd_bucket,
+    cmd_purge,
+    cmd_reload
+)]
+struct Owner;
+
+/// The names and aliases of the commands that aren't search sites, so no site can take them
+pub fn builtin_cmds() -> impl Iterator<Item = &'static str> {
+    cmd_help::CMD_HELP
+        .options
+        .names
+        .iter()
+        .chain(
+            GENERAL_GROUP
+                .options
+                .commands
+                .iter()
+                .chain(OWNER_GROUP.options.commands.iter())
+                .flat_map(|cmd| cmd.options.names.iter()),
+        )
+        .copied()
+}
 
 pub struct Handler;
 #[serenity::async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, info: Ready) {
-        ctx.set_activity(Activity::playing(
-            format!("@{} help", info.user.name).as_str(),
-        ))
-        .await;
+        ctx.set_activity(cmd_admin::activity(&info.user.name)).await;
+
+        if ctx.shard_id == 0 {
+            cmd_slash::register(&ctx).await;
+            cmd_reload::reload_on_sighup(&ctx);
+            cmd_bucket::prune_uses_periodically(&ctx);
+            cmd_block::reload_blocks_periodically(&ctx);
+        }
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: Guild, is_new: bool) {
+        if !is_new {
+            return;
+        }
+
+        match BotConfig::get() {
+            Some(config) => {
+                if config.log_guild_added() {
+                    logging::log_at(
+                        &ctx,
+                        Level::Info,
+                        format!(
+                            "Added to {} with {} members! In {} guilds now",
+                            guild.name,
+                            guild.member_count,
+                            ctx.cache.guild_count().await
+                        ),
+                    );
+                }
+            }
+            None => {
+                log(
+                    &ctx,
+                    "Couldn't get BotConfig to see if guild adds should be logged",
+                )
+                .await
+            }
+        }
+    }
+
+    async fn guild_delete(&self, ctx: Context, incomplete: GuildUnavailable, full: Option<Guild>) {
+        if incomplete.unavailable {
+            return;
+        }
+
+        match BotConfig::get() {
+            Some(config) => {
+                if config.log_guild_removed() {
+                    let guild = match &full {
+                        Some(guild) => {
+                            format!("{} with {} members", guild.name, guild.member_count)
+                        }
+                        None => incomplete.id.to_string(),
+                    };
+                    logging::log_at(
+                        &ctx,
+                        Level::Info,
+                        format!(
+                            "Removed from {} :( In {} guilds now",
+                            guild,
+                            ctx.cache.guild_count().await
+                        ),
+                    );
+                }
+            }
+            None => {
+                log(
+                    &ctx,
+                    "Couldn't get BotConfig to see if guild removals should be logged",
+                )
+                .await
+            }
+        }
+
+        cmd_purge::forget_guild_later(ctx, incomplete.id).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        cmd_slash::handle(&ctx, interaction).await;
     }
 
     async fn cache_ready(&self, ctx: Context, guilds: Vec<GuildId>) {
+        cmd_purge::set_cache_ready();
+
         if let Some(config) = BotConfig::get() {
             if config.log_guild_added() {
                 let msg = format!("In {} guilds!", guilds.len());
                 println!("{}", msg);
-                log(&ctx, msg).await;
+                logging::log_at(&ctx, Level::Info, msg);
             }
         } else {
             {
@@ -71,8 +200,7 @@ impl EventHandler for Handler {
     }
 }
 
-pub async fn send_embed(ctx: &Context, reply: &Message, is_error: bool, mut embed: CreateEmbed) {
-    let channel = reply.channel_id;
+pub async fn set_colour(ctx: &Context, embed: &mut CreateEmbed, is_error: bool) {
     if is_error {
         embed.colour(11534368);
     } else {
@@ -83,106 +211,223 @@ pub async fn send_embed(ctx: &Context, reply: &Message, is_error: bool, mut embe
             None => log(ctx, "Couldn't get BotConfig to get colour").await,
         };
     };
+}
+
+pub async fn send_embed(
+    ctx: &Context,
+    reply: &Message,
+    is_error: bool,
+    mut embed: CreateEmbed,
+) -> Option<Message> {
+    let channel = reply.channel_id;
+    set_colour(ctx, &mut embed, is_error).await;
 
-    if let Err(err) = channel.send_message(ctx, |m| m.set_embed(embed)).await {
-        if let Err(err) = channel
-            .say(ctx, format!("Oops, couldn't send the message 🤦‍♀️: {}", err))
+    let err = match channel.send_message(ctx, |m| m.set_embed(embed)).await {
+        Ok(sent) => return Some(sent),
+        Err(err) => err,
+    };
+    if let Err(err) = channel
+        .say(ctx, format!("Oops, couldn't send the message 🤦‍♀️: {}", err))
+        .await
+    {
+        if let Err(err) = reply
+            .author
+            .dm(ctx, |m| {
+                m.embed(|e| {
+                    e.colour(11534368)
+                        .description(format!("{}\nLet the admins know so they can fix it\n", err))
+                        .title(format!(
+                            "Looks like I can't send messages in {} :(",
+                            reply.channel_id.mention()
+                        ))
+                })
+            })
             .await
         {
-            if let Err(err) = reply
-                .author
-                .dm(ctx, |m| {
-                    m.embed(|e| {
-                        e.colour(11534368)
-                            .description(format!(
-                                "{}\nLet the admins know so they can fix it\n",
-                                err
-                            ))
-                            .title(format!(
-                                "Looks like I can't send messages in {} :(",
-                                reply.channel_id.mention()
-                            ))
-                    })
-                })
-                .await
-            {
-                log(
-                    ctx,
-                    format!(
-                        "Couldn't even send the message to inform the commander: {}",
-                        err
-                    ),
-                )
-                .await
-            }
+            log(
+                ctx,
+                format!(
+                    "Couldn't even send the message to inform the commander: {}",
+                    err
+                ),
+            )
+            .await
         }
     }
+    None
 }
 
-pub async fn log(ctx: &Context, msg: impl Display + AsRef<[u8]>) {
-    match BotInfo::get() {
-        Some(info) => match info.owner().create_dm_channel(ctx).await {
-            Ok(channel) => {
-                if let Err(err) = channel.say(ctx, &msg).await {
-                    print_and_write(format!(
-                        "Couldn't DM the owner when trying to log: {}\nMessage: {}",
-                        err, msg
-                    ));
-                }
-            }
-            Err(err) => print_and_write(format!(
-                "Couldn't get the DM channel with the owner when trying to log: {}\nMessage: {}",
-                err, msg
-            )),
-        },
-        None => print_and_write(format!(
-            "Couldn't get BotInfo when trying to log\nMessage: {}",
-            msg
-        )),
+pub async fn send_pages(ctx: &Context, reply: &Message, mut pages: Vec<CreateEmbed>) {
+    number_pages(&mut pages);
+
+    let first = match pages.first() {
+        Some(first) => first.clone(),
+        None => return,
+    };
+    let sent = match send_embed(ctx, reply, false, first).await {
+        Some(sent) => sent,
+        None => return,
     };
+
+    if let Err(err) = turn_pages(ctx, sent, reply.author.id, pages).await {
+        let mut embed = CreateEmbed::default();
+        embed
+            .title("I couldn't add the reactions to turn the pages..")
+            .description(format!(
+                "{}\nLet the admins know so they can let me add reactions",
+                err
+            ));
+        send_embed(ctx, reply, true, embed).await;
+    }
 }
 
-pub fn print_and_write(msg: impl Display) {
-    let mut print_and_write = format!(
-        "{}: {}\n\n",
-        chrono::Utc::now().format("%e %B %A %H:%M:%S"),
-        msg
-    );
-    println!("{}", print_and_write);
-
-    let log_file = match BotConfig::get() {
-        Some(config) => config.log_file(),
-        None => {
-            print_and_write += "Writing into a file named \"discord-base logs.txt\" because getting BOT_CONFIG also failed\n\n";
-            "discord-base logs.txt"
+pub fn number_pages(pages: &mut [CreateEmbed]) {
+    let page_count = pages.len();
+    for (i, page) in pages.iter_mut().enumerate() {
+        page.footer(|f| f.text(format!("Page {}/{}", i + 1, page_count)));
+        if let Some(config) = BotConfig::get() {
+            page.colour(config.colour());
         }
-    };
+    }
+}
 
-    match std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(log_file)
-    {
-        Ok(mut file) => {
-            if let Err(err) = file.write(print_and_write.as_bytes()) {
-                println!("Couldn't write to the log file: {}", err)
+pub async fn turn_pages(
+    ctx: &Context,
+    mut sent: Message,
+    author: UserId,
+    pages: Vec<CreateEmbed>,
+) -> serenity::Result<()> {
+    let page_count = pages.len();
+    if page_count < 2 {
+        return Ok(());
+    }
+
+    for emoji in ["⬅️", "➡️"].iter() {
+        sent.react(ctx, ReactionType::Unicode(emoji.to_string()))
+            .await?;
+    }
+
+    let mut collector = sent
+        .await_reactions(ctx)
+        .author_id(author)
+        .added(true)
+        .removed(true)
+        .timeout(Duration::from_secs(120))
+        .await;
+
+    let mut page = 0;
+    while let Some(action) = collector.next().await {
+        page = match &action.as_inner_ref().emoji {
+            ReactionType::Unicode(emoji) if emoji == "⬅️" => {
+                (page + page_count - 1) % page_count
+            }
+            ReactionType::Unicode(emoji) if emoji == "➡️" => (page + 1) % page_count,
+            _ => continue,
+        };
+        if let Err(err) = sent.edit(ctx, |m| m.set_embed(pages[page].clone())).await {
+            log(
+                ctx,
+                format!("Couldn't edit the message to turn the page: {}", err),
+            )
+            .await;
+            break;
+        }
+    }
+
+    if sent.delete_reactions(ctx).await.is_err() {
+        for emoji in ["⬅️", "➡️"].iter() {
+            if let Err(err) = sent
+                .channel_id
+                .delete_reaction(ctx, sent.id, None, ReactionType::Unicode(emoji.to_string()))
+                .await
+            {
+                log(ctx, format!("Couldn't remove the page reactions: {}", err)).await;
+                break;
             }
         }
-        Err(err) => println!("Couldn't open or create the log file: {}", err),
+    }
+
+    Ok(())
+}
+
+pub async fn log(ctx: &Context, msg: impl Display) {
+    logging::log_at(ctx, Level::Error, msg);
+}
+
+/// The database for the command named `cmd`, `None` after telling them it's gone
+pub async fn db(ctx: &Context, msg: &Message, cmd: &str) -> Option<AnyPool> {
+    let db = ctx.data.read().await.get::<DatabaseKey>().cloned();
+    if db.is_none() {
+        let mut embed = CreateEmbed::default();
+        lost_db(ctx, cmd, &mut embed).await;
+        send_embed(ctx, msg, true, embed).await;
+    }
+    db
+}
+
+/// The guild and the database for the command named `cmd` that changes something in the guild,
+/// `None` after writing what went wrong in the embed. `in_dms` is what to tell them if it's used
+/// in DMs somehow
+pub async fn guild_and_db(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    embed: &mut CreateEmbed,
+    cmd: &str,
+    in_dms: &str,
+) -> Option<(GuildId, AnyPool)> {
+    let db = ctx.data.read().await.get::<DatabaseKey>().cloned();
+
+    match (guild_id, db) {
+        (Some(guild_id), Some(db)) => Some((guild_id, db)),
+        (None, _) => {
+            log(ctx, format!("guild_id is None for the {} command", cmd)).await;
+            embed
+                .title("Something weird happened and I let you use this command in DMs")
+                .description(in_dms);
+            None
+        }
+        (_, None) => {
+            lost_db(ctx, cmd, embed).await;
+            None
+        }
     }
 }
 
-pub fn set_dir() {
-    match env::current_exe() {
-        Ok(path) => match path.parent() {
-            Some(parent) => {
-                if let Err(err) = env::set_current_dir(parent) {
-                    println!("Couldn't change the current directory: {}", err);
+async fn lost_db(ctx: &Context, cmd: &str, embed: &mut CreateEmbed) {
+    log(
+        ctx,
+        format!("Couldn't get the database for the {} command", cmd),
+    )
+    .await;
+    embed
+        .title("Now this is super weird and scary")
+        .description("I lost my whole book where I write things down, sorry..");
+}
+
+pub fn print_and_write(msg: impl Display) {
+    logging::print_and_write(Level::Error, msg);
+}
+
+/// Changes the current directory to `data_dir`, creating it if it isn't there, or to the exe's
+/// directory if there's no `data_dir`
+pub fn set_dir(data_dir: Option<&Path>) {
+    if let Some(data_dir) = data_dir {
+        if let Err(err) = fs::create_dir_all(data_dir).and_then(|_| env::set_current_dir(data_dir))
+        {
+            println!("Couldn't change the current directory: {}", err);
+        }
+    } else {
+        match env::current_exe() {
+            Ok(path) => match path.parent() {
+                Some(parent) => {
+                    if let Err(err) = env::set_current_dir(parent) {
+                        println!("Couldn't change the current directory: {}", err);
+                    }
                 }
-            }
-            None => println!("Couldn't get the directory of the exe"),
-        },
-        Err(err) => println!("Couldn't get the location of the exe: {}", err),
+                None => println!("Couldn't get the directory of the exe"),
+            },
+            Err(err) => println!("Couldn't get the location of the exe: {}", err),
+        }
     }
     match env::current_dir() {
         Ok(dir) => println!(
@@ -0,0 +1,482 @@
+use std::{
+    fmt::{self, Display},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    mem,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    framework::standard::{macros::hook, CommandResult},
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, UserId},
+    },
+};
+
+use crate::{
+    definitions::{truncate, CLIENT, MAX_DESCRIPTION_LEN},
+    globals::{check_url, BotConfig, BotInfo, ConfigProblem},
+};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum LogSink {
+    Owner {
+        #[serde(default)]
+        level: Level,
+    },
+    Channel {
+        channel_id: u64,
+        #[serde(default)]
+        level: Level,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        level: Level,
+    },
+    File {
+        #[serde(default)]
+        level: Level,
+    },
+    Stdout {
+        #[serde(default)]
+        level: Level,
+    },
+}
+
+impl LogSink {
+    fn level(&self) -> Level {
+        match self {
+            LogSink::Owner { level }
+            | LogSink::Channel { level, .. }
+            | LogSink::Webhook { level, .. }
+            | LogSink::File { level }
+            | LogSink::Stdout { level } => *level,
+        }
+    }
+}
+
+impl LogSink {
+    pub fn validate(&self, field: &str, problems: &mut Vec<ConfigProblem>) {
+        match self {
+            LogSink::Channel { channel_id: 0, .. } => problems.push(ConfigProblem::new(
+                format!("{}.channel_id", field),
+                0,
+                "It isn't a channel ID",
+                "Right click the channel with Developer Mode on and copy its ID",
+            )),
+            LogSink::Webhook { url, .. } => check_url(&format!("{}.url", field), url, problems),
+            _ => {}
+        }
+    }
+}
+
+pub fn default_log_sinks() -> Vec<LogSink> {
+    vec![LogSink::Owner { level: Level::Info }]
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    #[default]
+    Never,
+    Daily,
+    Size,
+}
+
+/// Where something happened, shown next to the logged message
+#[derive(Clone, Debug, Default)]
+pub struct LogContext {
+    pub guild_id: Option<GuildId>,
+    pub channel_id: Option<ChannelId>,
+    pub user_id: Option<UserId>,
+    pub command: Option<String>,
+}
+
+impl LogContext {
+    pub fn from_message(msg: &Message) -> Self {
+        LogContext {
+            guild_id: msg.guild_id,
+            channel_id: Some(msg.channel_id),
+            user_id: Some(msg.author.id),
+            command: None,
+        }
+    }
+
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.guild_id.is_none()
+            && self.channel_id.is_none()
+            && self.user_id.is_none()
+            && self.command.is_none()
+    }
+}
+
+impl Display for LogContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(guild_id) = self.guild_id {
+            fields.push(format!("guild={}", guild_id));
+        }
+        if let Some(channel_id) = self.channel_id {
+            fields.push(format!("channel={}", channel_id));
+        }
+        if let Some(user_id) = self.user_id {
+            fields.push(format!("user={}", user_id));
+        }
+        if let Some(command) = &self.command {
+            fields.push(format!("command={}", command));
+        }
+        f.write_str(&fields.join(" "))
+    }
+}
+
+struct Record {
+    time: DateTime<Utc>,
+    level: Level,
+    context: LogContext,
+    msg: String,
+}
+
+impl Record {
+    fn new(level: Level, context: LogContext, msg: impl Display) -> Self {
+        Record {
+            time: Utc::now(),
+            level,
+            context,
+            msg: msg.to_string(),
+        }
+    }
+
+    fn to_line(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text if self.context.is_empty() => format!(
+                "{} {} {}",
+                self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                self.level,
+                self.msg
+            ),
+            LogFormat::Text => format!(
+                "{} {} {}: {}",
+                self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                self.level,
+                self.context,
+                self.msg
+            ),
+            LogFormat::Json => serde_json::json!({
+                "timestamp": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                "level": self.level.to_string(),
+                "guild_id": self.context.guild_id.map(|id| id.0),
+                "channel_id": self.context.channel_id.map(|id| id.0),
+                "user_id": self.context.user_id.map(|id| id.0),
+                "command": self.context.command,
+                "message": self.msg,
+            })
+            .to_string(),
+        }
+    }
+}
+
+static QUEUE: Lazy<Mutex<Vec<Record>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static FLUSH_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+pub fn log_at(ctx: &Context, level: Level, msg: impl Display) {
+    log_with(ctx, level, LogContext::default(), msg);
+}
+
+/// Queues the message for the log sinks, the ones logged within `log_batch_seconds` are sent
+/// together
+pub fn log_with(ctx: &Context, level: Level, context: LogContext, msg: impl Display) {
+    let record = Record::new(level, context, msg);
+    match QUEUE.lock() {
+        Ok(mut queue) => queue.push(record),
+        Err(_) => {
+            write_file(&record);
+            return;
+        }
+    }
+
+    if FLUSH_SCHEDULED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let seconds = BotConfig::get().map_or(2, |config| config.log_batch_seconds());
+        tokio::time::sleep(Duration::from_secs(seconds)).await;
+        FLUSH_SCHEDULED.store(false, Ordering::Release);
+        flush(&ctx).await;
+    });
+}
+
+/// Sends everything queued right away, like before shutting down
+pub async fn flush(ctx: &Context) {
+    let records = match QUEUE.lock() {
+        Ok(mut queue) => mem::take(&mut *queue),
+        Err(_) => return,
+    };
+    if records.is_empty() {
+        return;
+    }
+
+    let config = BotConfig::get();
+    let default_sinks;
+    let sinks = match &config {
+        Some(config) => config.log_sinks(),
+        None => {
+            default_sinks = default_log_sinks();
+            &default_sinks
+        }
+    };
+
+    for sink in sinks.iter() {
+        let records: Vec<&Record> = records
+            .iter()
+            .filter(|record| record.level >= sink.level())
+            .collect();
+        if records.is_empty() {
+            continue;
+        }
+
+        let result = match sink {
+            LogSink::Owner { .. } => match BotInfo::get() {
+                Some(info) => match info.owner().create_dm_channel(ctx).await {
+                    Ok(channel) => send_channel(ctx, channel.id, &records).await,
+                    Err(err) => Err(format!(
+                        "Couldn't get the DM channel with the owner: {}",
+                        err
+                    )),
+                },
+                None => Err("Couldn't get BotInfo to DM the owner".to_string()),
+            },
+            LogSink::Channel { channel_id, .. } => {
+                send_channel(ctx, ChannelId(*channel_id), &records).await
+            }
+            LogSink::Webhook { url, .. } => send_webhook(url, &records).await,
+            LogSink::File { .. } => {
+                for record in records.iter() {
+                    write_file(record);
+                }
+                Ok(())
+            }
+            LogSink::Stdout { .. } => {
+                for record in records.iter() {
+                    println!("{}", record.to_line(log_format()));
+                }
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            print_and_write(Level::Error, format!("Couldn't log to a sink: {}", err));
+            for record in records {
+                print_and_write_record(record);
+            }
+        }
+    }
+}
+
+fn lines(records: &[&Record]) -> String {
+    records
+        .iter()
+        .map(|record| {
+            if record.context.is_empty() {
+                format!("**{}** {}", record.level, record.msg)
+            } else {
+                format!("**{}** {}\n*{}*", record.level, record.msg, record.context)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn embed(records: &[&Record]) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    if records.len() > 1 {
+        embed.title(format!("{} things happened", records.len()));
+    }
+    embed.description(truncate(&lines(records), MAX_DESCRIPTION_LEN));
+
+    let is_error = records.iter().any(|record| record.level >= Level::Warn);
+    if is_error {
+        embed.colour(11534368);
+    } else if let Some(config) = BotConfig::get() {
+        embed.colour(config.colour());
+    }
+    embed
+}
+
+async fn send_channel(
+    ctx: &Context,
+    channel_id: ChannelId,
+    records: &[&Record],
+) -> Result<(), String> {
+    let embed = embed(records);
+    channel_id
+        .send_message(ctx, |m| m.set_embed(embed))
+        .await
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+async fn send_webhook(url: &str, records: &[&Record]) -> Result<(), String> {
+    let embed = serenity::utils::hashmap_to_json_map(embed(records).0);
+    CLIENT
+        .post(url)
+        .json(&serde_json::json!({ "embeds": [embed] }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn log_format() -> LogFormat {
+    BotConfig::get().map_or(LogFormat::Text, |config| config.log_format())
+}
+
+/// Prints the message and writes it to the log file, without going through the log sinks
+pub fn print_and_write(level: Level, msg: impl Display) {
+    print_and_write_record(&Record::new(level, LogContext::default(), msg));
+}
+
+fn print_and_write_record(record: &Record) {
+    println!("{}", record.to_line(log_format()));
+    write_file(record);
+}
+
+static FILE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn write_file(record: &Record) {
+    let (log_file, rotation, max_bytes, keep) = match BotConfig::get() {
+        Some(config) => (
+            config.log_file().clone(),
+            config.log_rotation(),
+            config.log_max_bytes(),
+            config.log_keep(),
+        ),
+        None => ("search-logs.txt".to_string(), Rotation::Never, 0, 0),
+    };
+    let line = record.to_line(log_format()) + "\n";
+
+    let _lock = FILE_LOCK.lock();
+    if let Err(err) = rotate(&log_file, rotation, max_bytes, keep, line.len() as u64) {
+        println!("Couldn't rotate the log file: {}", err);
+    }
+
+    match OpenOptions::new().create(true).append(true).open(&log_file) {
+        Ok(mut file) => {
+            if let Err(err) = file.write_all(line.as_bytes()) {
+                println!("Couldn't write to the log file: {}", err)
+            }
+        }
+        Err(err) => println!("Couldn't open or create the log file: {}", err),
+    }
+}
+
+/// Renames the log file to `[log_file].1` and the older ones up by one if it's time, deleting the
+/// ones after `keep`
+fn rotate(
+    log_file: &str,
+    rotation: Rotation,
+    max_bytes: u64,
+    keep: usize,
+    next_len: u64,
+) -> io::Result<()> {
+    let metadata = match fs::metadata(log_file) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let should_rotate = match rotation {
+        Rotation::Never => false,
+        Rotation::Size => metadata.len() > 0 && metadata.len() + next_len > max_bytes,
+        Rotation::Daily => {
+            let modified: DateTime<Utc> = metadata.modified()?.into();
+            modified.date_naive() != Utc::now().date_naive()
+        }
+    };
+    if !should_rotate {
+        return Ok(());
+    }
+
+    if keep == 0 {
+        return fs::remove_file(log_file);
+    }
+    let _ = fs::remove_file(format!("{}.{}", log_file, keep));
+    for i in (1..keep).rev() {
+        let from = format!("{}.{}", log_file, i);
+        if Path::new(&from).exists() {
+            fs::rename(&from, format!("{}.{}", log_file, i + 1))?;
+        }
+    }
+    fs::rename(log_file, format!("{}.1", log_file))
+}
+
+#[hook]
+pub async fn before(ctx: &Context, msg: &Message, cmd_name: &str) -> bool {
+    log_with(
+        ctx,
+        Level::Debug,
+        LogContext::from_message(msg).command(cmd_name),
+        "Running the command",
+    );
+    true
+}
+
+#[hook]
+pub async fn after(ctx: &Context, msg: &Message, cmd_name: &str, result: CommandResult) {
+    if let Err(err) = result {
+        log_with(
+            ctx,
+            Level::Error,
+            LogContext::from_message(msg).command(cmd_name),
+            format!("The command failed: {}", err),
+        );
+    }
+}
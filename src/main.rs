@@ -1,33 +1,51 @@
-use serenity::{
-    client::bridge::gateway::GatewayIntents,
-    framework::{standard::buckets::LimitedFor, StandardFramework},
-    Client,
-};
+use std::process;
+
+use serenity::{client::bridge::gateway::GatewayIntents, framework::StandardFramework, Client};
 
 use discord_search::{
-    cmd_error,
+    cli::CliArgs,
+    cmd_admin, cmd_block, cmd_bucket, cmd_channel, cmd_error,
     cmd_help::CMD_HELP,
-    cmd_prefix::prefix_check,
-    cmd_search,
-    globals::{set_db, BotConfig, BotInfo, CmdInfo, SqlitePoolKey},
-    print_and_write, set_dir, Handler, GENERAL_GROUP, MASTER_GROUP, SEARCH_GROUP,
+    cmd_prefix::{load_prefixes, prefix_check},
+    cmd_search, cmd_site,
+    globals::{report, set_db, BotConfig, BotInfo, CmdInfo, DatabaseKey, ShardManagerKey},
+    logging, print_and_write, set_dir, Handler, GENERAL_GROUP, OWNER_GROUP,
 };
 
 #[tokio::main]
 async fn main() {
-    set_dir();
+    cmd_admin::set_started_at();
+    let args = CliArgs::parse();
+    set_dir(args.data_dir());
 
-    cmd_search::set_sites();
+    if args.check_config() {
+        match BotConfig::load(&args.config()) {
+            Ok(_) => println!("The config looks good!"),
+            Err(problems) => {
+                println!("{}", report(&problems));
+                process::exit(1);
+            }
+        }
+        return;
+    }
 
-    BotConfig::set("search-config.toml");
+    BotConfig::set(&args.config());
     let config = BotConfig::get().expect("Couldn't access BOT_CONFIG to get the token");
 
+    cmd_search::set_sites();
+    let search_group = cmd_search::search_group().expect("Couldn't access the search group");
+
     BotInfo::set(config.token()).await;
     let bot_info = BotInfo::get().expect("Couldn't access BOT_INFO to get the owner and bot ID");
 
-    CmdInfo::set();
+    CmdInfo::set(&[&GENERAL_GROUP, &OWNER_GROUP, search_group]);
 
     let db = set_db().await;
+    load_prefixes(&db).await;
+    cmd_site::load_guild_sites(&db).await;
+    cmd_channel::load_channel_settings(&db).await;
+    cmd_bucket::load_bucket_overrides(&db).await;
+    cmd_block::load_blocks(&db).await;
 
     let framework = StandardFramework::new()
         .configure(|c| {
@@ -38,41 +56,37 @@ async fn main() {
                 .owners(vec![bot_info.owner()].into_iter().collect())
                 .dynamic_prefix(|ctx, msg| Box::pin(prefix_check(ctx, msg)))
         })
+        .before(logging::before)
+        .after(logging::after)
         .on_dispatch_error(cmd_error::handle)
-        .bucket("general", |b| {
-            b.limit_for(LimitedFor::Channel)
-                .await_ratelimits(1)
-                .delay_action(cmd_error::delay_action)
-                .time_span(600)
-                .limit(10)
-        })
-        .await
-        .bucket("expensive", |b| {
-            b.limit_for(LimitedFor::Guild)
-                .await_ratelimits(1)
-                .delay_action(cmd_error::delay_action)
-                .time_span(3600)
-                .limit(10)
-        })
-        .await
+        .unrecognised_command(cmd_site::guild_site)
         .help(&CMD_HELP)
         .group(&GENERAL_GROUP)
-        .group(&MASTER_GROUP)
-        .group(&SEARCH_GROUP);
+        .group(&OWNER_GROUP)
+        .group(search_group);
 
-    let mut client = Client::builder(&config.token())
+    let mut client = Client::builder(config.token())
         .intents(
             GatewayIntents::GUILD_MESSAGES
                 | GatewayIntents::DIRECT_MESSAGES
+                | GatewayIntents::GUILD_MESSAGE_REACTIONS
+                | GatewayIntents::DIRECT_MESSAGE_REACTIONS
                 | GatewayIntents::GUILDS,
         )
+        .application_id(bot_info.user().0)
         .event_handler(Handler)
-        .type_map_insert::<SqlitePoolKey>(db)
+        .type_map_insert::<DatabaseKey>(db.clone())
         .framework(framework)
         .await
         .expect("Couldn't create the client");
+    client
+        .data
+        .write()
+        .await
+        .insert::<ShardManagerKey>(client.shard_manager.clone());
 
     if let Err(e) = client.start_autosharded().await {
         print_and_write(format!("Couldn't start the client: {}", e));
     }
+    db.close().await;
 }
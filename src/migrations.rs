@@ -0,0 +1,482 @@
+use sqlx::{
+    any::{AnyConnection, AnyKind, AnyPool},
+    query, Executor, Row,
+};
+
+/// The key of the PostgreSQL advisory lock held while migrating, any number works as long as
+/// nothing else uses it
+const MIGRATION_LOCK: i64 = 0x6473_6d69_6772_6174;
+
+/// Every change to the SQLite database, in order. Migration `n` here takes the database to version `n + 1`
+///
+/// Never change or remove one that's already released, add a new one to the end instead
+const SQLITE_MIGRATIONS: &[&str] = &[
+    // 1: The prefix per guild
+    "CREATE TABLE IF NOT EXISTS prefixes (
+        guild_id INTEGER PRIMARY KEY,
+        prefix TEXT
+    ) WITHOUT ROWID;",
+    // 2: The search commands added to guilds
+    "CREATE TABLE IF NOT EXISTS guild_sites (
+        guild_id INTEGER,
+        name TEXT,
+        url TEXT NOT NULL,
+        icon TEXT,
+        PRIMARY KEY (guild_id, name)
+    ) WITHOUT ROWID;",
+    // 3: More than one prefix per guild
+    "CREATE TABLE IF NOT EXISTS guild_prefixes (
+        guild_id INTEGER,
+        prefix TEXT,
+        PRIMARY KEY (guild_id, prefix)
+    ) WITHOUT ROWID;
+    INSERT OR IGNORE INTO guild_prefixes (guild_id, prefix)
+        SELECT guild_id, prefix FROM prefixes WHERE prefix IS NOT NULL;
+    DROP TABLE prefixes;",
+    // 4: The group prefix per guild
+    "CREATE TABLE IF NOT EXISTS group_prefixes (
+        guild_id INTEGER PRIMARY KEY,
+        group_prefix TEXT NOT NULL
+    ) WITHOUT ROWID;",
+    // 5: The prefix and disabled commands per channel
+    "CREATE TABLE IF NOT EXISTS channel_settings (
+        channel_id INTEGER PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        prefix TEXT,
+        disabled_cmds TEXT NOT NULL DEFAULT ''
+    ) WITHOUT ROWID;",
+    // 6: The rate limits per guild that are different from the config
+    "CREATE TABLE IF NOT EXISTS bucket_overrides (
+        guild_id INTEGER,
+        bucket TEXT,
+        uses INTEGER NOT NULL,
+        time_span INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, bucket)
+    ) WITHOUT ROWID;",
+    // 7: The uses of the rate limits, so restarting doesn't reset them
+    "CREATE TABLE IF NOT EXISTS rate_limit_uses (
+        bucket TEXT NOT NULL,
+        scope_id INTEGER NOT NULL,
+        used_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS rate_limit_uses_scope ON rate_limit_uses (bucket, scope_id, used_at);
+    CREATE INDEX IF NOT EXISTS rate_limit_uses_expiry ON rate_limit_uses (expires_at);",
+    // 8: The users, guilds and channels the owner blocked
+    "CREATE TABLE IF NOT EXISTS blocks (
+        kind TEXT,
+        id INTEGER,
+        reason TEXT,
+        expires_at INTEGER,
+        PRIMARY KEY (kind, id)
+    ) WITHOUT ROWID;",
+];
+
+/// The same changes as [`SQLITE_MIGRATIONS`] for PostgreSQL, they have to stay in sync
+const POSTGRES_MIGRATIONS: &[&str] = &[
+    // 1: The prefix per guild
+    "CREATE TABLE IF NOT EXISTS prefixes (
+        guild_id BIGINT PRIMARY KEY,
+        prefix TEXT
+    );",
+    // 2: The search commands added to guilds
+    "CREATE TABLE IF NOT EXISTS guild_sites (
+        guild_id BIGINT,
+        name TEXT,
+        url TEXT NOT NULL,
+        icon TEXT,
+        PRIMARY KEY (guild_id, name)
+    );",
+    // 3: More than one prefix per guild
+    "CREATE TABLE IF NOT EXISTS guild_prefixes (
+        guild_id BIGINT,
+        prefix TEXT,
+        PRIMARY KEY (guild_id, prefix)
+    );
+    INSERT INTO guild_prefixes (guild_id, prefix)
+        SELECT guild_id, prefix FROM prefixes WHERE prefix IS NOT NULL
+        ON CONFLICT DO NOTHING;
+    DROP TABLE prefixes;",
+    // 4: The group prefix per guild
+    "CREATE TABLE IF NOT EXISTS group_prefixes (
+        guild_id BIGINT PRIMARY KEY,
+        group_prefix TEXT NOT NULL
+    );",
+    // 5: The prefix and disabled commands per channel
+    "CREATE TABLE IF NOT EXISTS channel_settings (
+        channel_id BIGINT PRIMARY KEY,
+        guild_id BIGINT NOT NULL,
+        prefix TEXT,
+        disabled_cmds TEXT NOT NULL DEFAULT ''
+    );",
+    // 6: The rate limits per guild that are different from the config
+    "CREATE TABLE IF NOT EXISTS bucket_overrides (
+        guild_id BIGINT,
+        bucket TEXT,
+        uses BIGINT NOT NULL,
+        time_span BIGINT NOT NULL,
+        PRIMARY KEY (guild_id, bucket)
+    );",
+    // 7: The uses of the rate limits, so restarting doesn't reset them
+    "CREATE TABLE IF NOT EXISTS rate_limit_uses (
+        bucket TEXT NOT NULL,
+        scope_id BIGINT NOT NULL,
+        used_at BIGINT NOT NULL,
+        expires_at BIGINT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS rate_limit_uses_scope ON rate_limit_uses (bucket, scope_id, used_at);
+    CREATE INDEX IF NOT EXISTS rate_limit_uses_expiry ON rate_limit_uses (expires_at);",
+    // 8: The users, guilds and channels the owner blocked
+    "CREATE TABLE IF NOT EXISTS blocks (
+        kind TEXT,
+        id BIGINT,
+        reason TEXT,
+        expires_at BIGINT,
+        PRIMARY KEY (kind, id)
+    );",
+];
+
+const _: () = assert!(SQLITE_MIGRATIONS.len() == POSTGRES_MIGRATIONS.len());
+
+/// The version the database will be in after [`migrate`]
+pub fn latest_version() -> i64 {
+    SQLITE_MIGRATIONS.len() as i64
+}
+
+/// Runs the migrations the database doesn't have yet, each one in its own transaction
+///
+/// Processes sharing a PostgreSQL database take turns so only one of them runs each migration.
+/// Panics if the database is newer than the ones this version knows about
+pub async fn migrate(db: &AnyPool) {
+    let (schema_version_table, migrations) = match db.any_kind() {
+        AnyKind::Sqlite => (
+            "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            applied_at TEXT NOT NULL
+        ) WITHOUT ROWID",
+            SQLITE_MIGRATIONS,
+        ),
+        AnyKind::Postgres => (
+            "CREATE TABLE IF NOT EXISTS schema_version (
+            version BIGINT PRIMARY KEY,
+            applied_at TEXT NOT NULL
+        )",
+            POSTGRES_MIGRATIONS,
+        ),
+    };
+
+    loop {
+        let mut transaction = db
+            .begin()
+            .await
+            .expect("Couldn't start a transaction to migrate the database");
+        if db.any_kind() == AnyKind::Postgres {
+            // Released when the transaction ends, the others read the new version after that
+            query("SELECT pg_advisory_xact_lock($1)")
+                .bind(MIGRATION_LOCK)
+                .execute(&mut transaction)
+                .await
+                .expect("Couldn't lock the database to migrate it");
+        }
+        query(schema_version_table)
+            .execute(&mut transaction)
+            .await
+            .expect("Couldn't create the schema version table");
+
+        let version = schema_version(&mut transaction)
+            .await
+            .expect("Couldn't get the schema version");
+        if version > latest_version() {
+            panic!(
+                "The database's schema version is {} but I only know up to {}, are you running an older version of me?",
+                version,
+                latest_version()
+            );
+        }
+        let migration = match migrations.get(version as usize) {
+            Some(migration) => *migration,
+            None => {
+                transaction
+                    .commit()
+                    .await
+                    .expect("Couldn't finish checking the schema version");
+                return;
+            }
+        };
+
+        let version = version + 1;
+        transaction.execute(migration).await.unwrap_or_else(|err| {
+            panic!(
+                "Couldn't migrate the database to version {}: {}",
+                version, err
+            )
+        });
+        query("INSERT INTO schema_version (version, applied_at) VALUES($1, $2)")
+            .bind(version)
+            .bind(chrono::Utc::now().to_rfc3339())
+            .execute(&mut transaction)
+            .await
+            .expect("Couldn't write the schema version");
+        transaction.commit().await.unwrap_or_else(|err| {
+            panic!(
+                "Couldn't migrate the database to version {}: {}",
+                version, err
+            )
+        });
+    }
+}
+
+/// The version the database is in now, it's [`latest_version`] unless another process migrated it
+/// further
+pub async fn database_version(db: &AnyPool) -> sqlx::Result<i64> {
+    let mut conn = db.acquire().await?;
+    schema_version(&mut conn).await
+}
+
+/// The version the database is in, SQLite databases from before there were migrations are version
+/// 1 if they have the prefix table
+async fn schema_version(conn: &mut AnyConnection) -> sqlx::Result<i64> {
+    let version: Option<i64> = query("SELECT MAX(version) FROM schema_version")
+        .fetch_one(&mut *conn)
+        .await?
+        .try_get(0)?;
+    if let Some(version) = version {
+        return Ok(version);
+    }
+    if conn.kind() != AnyKind::Sqlite {
+        return Ok(0);
+    }
+
+    let has_prefixes =
+        query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'prefixes'")
+            .fetch_optional(conn)
+            .await?
+            .is_some();
+    Ok(if has_prefixes { 1 } else { 0 })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::str::FromStr;
+
+    use sqlx::any::{AnyConnectOptions, AnyPoolOptions};
+
+    use super::*;
+
+    /// Guilds and their prefixes, including one that turned the prefix off
+    const PREFIXES: [(i64, &str); 3] = [
+        (81384788765712384, "?"),
+        (172018499005317120, ""),
+        (228406572756369408, "search!"),
+    ];
+
+    async fn memory_db() -> AnyPool {
+        // One connection since every connection gets its own in-memory database
+        AnyPoolOptions::new()
+            .max_connections(1)
+            .connect_with(AnyConnectOptions::from_str("sqlite::memory:").expect("Bad database URL"))
+            .await
+            .expect("Couldn't open the database")
+    }
+
+    /// A database like the ones from before there were migrations
+    async fn v1_db() -> AnyPool {
+        let db = memory_db().await;
+        query(
+            "CREATE TABLE IF NOT EXISTS prefixes (
+            guild_id INTEGER PRIMARY KEY,
+            prefix TEXT
+        ) WITHOUT ROWID",
+        )
+        .execute(&db)
+        .await
+        .expect("Couldn't create the prefixes table");
+
+        for (guild_id, prefix) in PREFIXES.iter() {
+            query("INSERT OR REPLACE INTO prefixes (guild_id, prefix) VALUES($1, $2)")
+                .bind(guild_id)
+                .bind(*prefix)
+                .execute(&db)
+                .await
+                .expect("Couldn't add a prefix");
+        }
+        db
+    }
+
+    async fn current_version(db: &AnyPool) -> i64 {
+        database_version(db)
+            .await
+            .expect("Couldn't get the schema version")
+    }
+
+    async fn guild_prefixes(db: &AnyPool) -> Vec<(i64, String)> {
+        query("SELECT guild_id, prefix FROM guild_prefixes ORDER BY guild_id")
+            .fetch_all(db)
+            .await
+            .expect("Couldn't get the prefixes")
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn keeps_the_prefixes_from_v1() {
+        let db = v1_db().await;
+        migrate(&db).await;
+        assert_eq!(current_version(&db).await, latest_version());
+        let expected: Vec<(i64, String)> = PREFIXES
+            .iter()
+            .map(|(guild_id, prefix)| (*guild_id, prefix.to_string()))
+            .collect();
+        assert_eq!(guild_prefixes(&db).await, expected);
+
+        let old_table =
+            query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'prefixes'")
+                .fetch_optional(&db)
+                .await
+                .unwrap();
+        assert!(old_table.is_none());
+
+        // Nothing to do the second time
+        migrate(&db).await;
+        assert_eq!(current_version(&db).await, latest_version());
+        assert_eq!(guild_prefixes(&db).await, expected);
+    }
+
+    #[tokio::test]
+    async fn migrates_a_new_database() {
+        let db = memory_db().await;
+        migrate(&db).await;
+        assert_eq!(current_version(&db).await, latest_version());
+        assert!(guild_prefixes(&db).await.is_empty());
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "are you running an older version of me?")]
+    async fn refuses_newer_databases() {
+        let db = memory_db().await;
+        migrate(&db).await;
+        query("INSERT INTO schema_version (version, applied_at) VALUES($1, $2)")
+            .bind(latest_version() + 1)
+            .bind(chrono::Utc::now().to_rfc3339())
+            .execute(&db)
+            .await
+            .expect("Couldn't write the schema version");
+
+        migrate(&db).await;
+    }
+
+    /// Needs `POSTGRES_TEST_URL` to be a PostgreSQL database it can wipe
+    #[cfg(feature = "postgres-tests")]
+    pub(crate) mod postgres {
+        use std::env;
+
+        use once_cell::sync::Lazy;
+        use tokio::sync::Mutex;
+
+        use super::*;
+
+        /// The tests in every module share the database, so they hold this while using it
+        pub(crate) static DATABASE: Lazy<Mutex<()>> = Lazy::new(Mutex::default);
+
+        const TABLES: [&str; 9] = [
+            "schema_version",
+            "prefixes",
+            "guild_sites",
+            "guild_prefixes",
+            "group_prefixes",
+            "channel_settings",
+            "bucket_overrides",
+            "rate_limit_uses",
+            "blocks",
+        ];
+
+        pub(crate) async fn connect() -> AnyPool {
+            let url = env::var("POSTGRES_TEST_URL")
+                .expect("Set POSTGRES_TEST_URL to a PostgreSQL database I can wipe");
+            AnyPool::connect(&url)
+                .await
+                .expect("Couldn't connect to POSTGRES_TEST_URL")
+        }
+
+        pub(crate) async fn wipe(db: &AnyPool) {
+            for table in TABLES.iter() {
+                query(&format!("DROP TABLE IF EXISTS {}", table))
+                    .execute(db)
+                    .await
+                    .expect("Couldn't drop a table");
+            }
+        }
+
+        // Both in one test since they wipe the same database
+        #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+        async fn migrates_version_1_once_from_many_processes() {
+            let _database = DATABASE.lock().await;
+            let db = connect().await;
+            wipe(&db).await;
+
+            // A database that's still at version 1
+            query(
+                "CREATE TABLE schema_version (
+                version BIGINT PRIMARY KEY,
+                applied_at TEXT NOT NULL
+            )",
+            )
+            .execute(&db)
+            .await
+            .unwrap();
+            query("INSERT INTO schema_version (version, applied_at) VALUES(1, 'before')")
+                .execute(&db)
+                .await
+                .unwrap();
+            db.execute(POSTGRES_MIGRATIONS[0]).await.unwrap();
+            for (guild_id, prefix) in PREFIXES.iter() {
+                query("INSERT INTO prefixes (guild_id, prefix) VALUES($1, $2)")
+                    .bind(guild_id)
+                    .bind(*prefix)
+                    .execute(&db)
+                    .await
+                    .unwrap();
+            }
+
+            // Their own pools like separate processes starting together
+            let processes: Vec<_> = (0..4)
+                .map(|_| {
+                    tokio::spawn(async {
+                        let db = connect().await;
+                        migrate(&db).await;
+                    })
+                })
+                .collect();
+            for process in processes {
+                process.await.expect("A process couldn't migrate");
+            }
+
+            assert_eq!(current_version(&db).await, latest_version());
+            let versions: i64 = query("SELECT COUNT(*) FROM schema_version")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+                .get(0);
+            assert_eq!(versions, latest_version());
+            let expected: Vec<(i64, String)> = PREFIXES
+                .iter()
+                .map(|(guild_id, prefix)| (*guild_id, prefix.to_string()))
+                .collect();
+            assert_eq!(guild_prefixes(&db).await, expected);
+
+            query("INSERT INTO schema_version (version, applied_at) VALUES($1, 'later')")
+                .bind(latest_version() + 1)
+                .execute(&db)
+                .await
+                .unwrap();
+            let newer = tokio::spawn(async {
+                let db = connect().await;
+                migrate(&db).await;
+            });
+            assert!(newer.await.is_err(), "It migrated a newer database");
+
+            wipe(&db).await;
+        }
+    }
+}